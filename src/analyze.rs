@@ -0,0 +1,282 @@
+//! Disk usage analysis.
+//!
+//! Walks a directory tree in parallel, in the spirit of `du` and `ncdu`, and aggregates the
+//! apparent size (the length of each file) and the allocated size (the blocks actually reserved on
+//! disk) per directory. Only the heaviest directories and files are kept in the result.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+/// The size of a file or of everything below a directory, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Size {
+    /// The sum of file lengths, as reported by `ls -l`.
+    pub apparent: u64,
+    /// The space reserved on disk, as reported by `du`.
+    pub allocated: u64,
+}
+
+impl Size {
+    /// Returns the size of a single directory entry.
+    fn of(metadata: &fs::Metadata) -> Self {
+        // `st_blocks` is always counted in 512-byte units, regardless of the filesystem block size.
+        Self { apparent: metadata.len(), allocated: metadata.blocks() * 512 }
+    }
+
+    /// Adds `other` to this size.
+    fn add(&mut self, other: Size) {
+        self.apparent += other.apparent;
+        self.allocated += other.allocated;
+    }
+}
+
+/// A file or directory together with its size.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    /// The path of the file or directory.
+    pub path: PathBuf,
+    /// The size of the file, or of everything below the directory.
+    pub size: Size,
+}
+
+/// Options controlling a scan.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanOptions {
+    /// Do not descend into directories on other filesystems than the root.
+    pub one_file_system: bool,
+    /// The number of heaviest directories and files to keep.
+    pub top: usize,
+}
+
+/// The result of scanning a directory tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Analysis {
+    /// The scanned root and the size of everything below it.
+    pub root: Entry,
+    /// The heaviest directories by allocated size, heaviest first. Sizes include subdirectories.
+    pub directories: Vec<Entry>,
+    /// The heaviest files by allocated size, heaviest first.
+    pub files: Vec<Entry>,
+    /// The number of directories scanned.
+    pub directory_count: u64,
+    /// The number of files and other non-directory entries scanned.
+    pub file_count: u64,
+    /// The number of entries that could not be read, e.g. because of missing permissions.
+    pub errors: u64,
+}
+
+/// Scans the tree below `root` using one thread per available CPU.
+///
+/// Hard-linked files are counted once. Symbolic links are not followed.
+///
+/// # Errors
+///
+/// Returns an error if `root` itself cannot be read. Unreadable entries below it are only counted
+/// in `Analysis::errors`.
+pub fn scan(root: &Path, options: ScanOptions) -> Result<Analysis> {
+    let metadata = fs::symlink_metadata(root).with_context(|| format!("Failed to read {}", root.display()))?;
+    let root_device = metadata.dev();
+
+    let queue = WorkQueue::new(root.to_path_buf());
+    let hard_links = Mutex::new(HashSet::new());
+    let workers = thread::available_parallelism().map_or(4, |n| n.get());
+
+    let results: Vec<Worker> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut worker = Worker::new(options.top);
+                    while let Some(dir) = queue.pop() {
+                        let subdirs = worker.visit(&dir, root_device, options.one_file_system, &hard_links);
+                        queue.finish(subdirs);
+                    }
+                    worker
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().expect("scan worker panicked")).collect()
+    });
+
+    // Merge the per-thread results.
+    let mut own_sizes: HashMap<PathBuf, Size> = HashMap::new();
+    let mut files = BinaryHeap::new();
+    let (mut file_count, mut errors) = (0, 0);
+    for worker in results {
+        own_sizes.extend(worker.directories);
+        file_count += worker.file_count;
+        errors += worker.errors;
+        for file in worker.files {
+            push_bounded(&mut files, file, options.top);
+        }
+    }
+    let directory_count = own_sizes.len() as u64;
+
+    // Add every directory to its parent, deepest first, so each total includes its subdirectories.
+    let mut directories: Vec<(PathBuf, Size)> = own_sizes.into_iter().collect();
+    directories.sort_by_key(|(path, _)| Reverse(path.components().count()));
+    let mut totals: HashMap<PathBuf, Size> = HashMap::with_capacity(directories.len());
+    for (path, own) in directories {
+        let total = totals.entry(path.clone()).or_default();
+        total.add(own);
+        let total = *total;
+        if path != root {
+            if let Some(parent) = path.parent() {
+                totals.entry(parent.to_path_buf()).or_default().add(total);
+            }
+        }
+    }
+
+    let root_size = totals.get(root).copied().unwrap_or_default();
+    let mut directories = BinaryHeap::new();
+    for (path, size) in totals {
+        push_bounded(&mut directories, Reverse((size.allocated, size.apparent, path)), options.top);
+    }
+
+    Ok(Analysis {
+        root: Entry { path: root.to_path_buf(), size: root_size },
+        directories: into_entries(directories),
+        files: into_entries(files),
+        directory_count,
+        file_count,
+        errors,
+    })
+}
+
+/// A min-heap entry ordered by allocated size, then apparent size, then path.
+type Ranked = Reverse<(u64, u64, PathBuf)>;
+
+/// Pushes `item` onto the min-heap, dropping the smallest element once it holds more than `limit`.
+fn push_bounded(heap: &mut BinaryHeap<Ranked>, item: Ranked, limit: usize) {
+    heap.push(item);
+    if heap.len() > limit {
+        heap.pop();
+    }
+}
+
+/// Converts a bounded min-heap into entries, heaviest first.
+fn into_entries(heap: BinaryHeap<Ranked>) -> Vec<Entry> {
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((allocated, apparent, path))| Entry { path, size: Size { apparent, allocated } })
+        .collect()
+}
+
+/// The state of a single scanning thread.
+struct Worker {
+    /// The size of each visited directory itself and the non-directory entries directly inside it.
+    directories: HashMap<PathBuf, Size>,
+    /// The heaviest files seen by this thread.
+    files: BinaryHeap<Ranked>,
+    file_count: u64,
+    errors: u64,
+    top: usize,
+}
+
+impl Worker {
+    fn new(top: usize) -> Self {
+        Self { directories: HashMap::new(), files: BinaryHeap::new(), file_count: 0, errors: 0, top }
+    }
+
+    /// Reads a single directory and returns the subdirectories that still need to be visited.
+    fn visit(
+        &mut self,
+        dir: &Path,
+        root_device: u64,
+        one_file_system: bool,
+        hard_links: &Mutex<HashSet<(u64, u64)>>,
+    ) -> Vec<PathBuf> {
+        let mut own = match fs::symlink_metadata(dir) {
+            Ok(metadata) => Size::of(&metadata),
+            Err(_) => {
+                self.errors += 1;
+                return Vec::new();
+            }
+        };
+        let mut subdirs = Vec::new();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => {
+                self.errors += 1;
+                self.directories.insert(dir.to_path_buf(), own);
+                return subdirs;
+            }
+        };
+        for entry in entries {
+            let Ok(entry) = entry else {
+                self.errors += 1;
+                continue;
+            };
+            let path = entry.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                self.errors += 1;
+                continue;
+            };
+
+            if metadata.is_dir() {
+                if !one_file_system || metadata.dev() == root_device {
+                    subdirs.push(path);
+                }
+                continue;
+            }
+
+            self.file_count += 1;
+            if metadata.nlink() > 1 && !hard_links.lock().unwrap().insert((metadata.dev(), metadata.ino())) {
+                continue;
+            }
+            let size = Size::of(&metadata);
+            own.add(size);
+            push_bounded(&mut self.files, Reverse((size.allocated, size.apparent, path)), self.top);
+        }
+
+        self.directories.insert(dir.to_path_buf(), own);
+        subdirs
+    }
+}
+
+/// The directories waiting to be visited, shared by all scanning threads.
+struct WorkQueue {
+    /// The pending directories and the number of directories currently being visited.
+    state: Mutex<(Vec<PathBuf>, usize)>,
+    ready: Condvar,
+}
+
+impl WorkQueue {
+    fn new(root: PathBuf) -> Self {
+        Self { state: Mutex::new((vec![root], 0)), ready: Condvar::new() }
+    }
+
+    /// Takes the next directory to visit, waiting while other threads may still add more. Returns
+    /// `None` once the whole tree has been visited.
+    fn pop(&self) -> Option<PathBuf> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(dir) = state.0.pop() {
+                state.1 += 1;
+                return Some(dir);
+            }
+            if state.1 == 0 {
+                return None;
+            }
+            state = self.ready.wait(state).unwrap();
+        }
+    }
+
+    /// Marks a directory as visited, queueing the subdirectories found in it.
+    fn finish(&self, subdirs: Vec<PathBuf>) {
+        let mut state = self.state.lock().unwrap();
+        state.0.extend(subdirs);
+        state.1 -= 1;
+        self.ready.notify_all();
+    }
+}
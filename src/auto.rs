@@ -0,0 +1,100 @@
+//! Threshold-triggered cleanups.
+//!
+//! `auto --when-used-above 85% --mount /` is meant to run from cron or a systemd timer. It only
+//! does work when the filesystem is fuller than the threshold, and then runs the low-risk tasks
+//! listed under `[auto] tasks` in the configuration without asking anything. The exit code tells
+//! the scheduler what happened, so that it can alert when cleaning up was not enough:
+//!
+//! | Exit code | Status                                             |
+//! |-----------|----------------------------------------------------|
+//! | 0         | The usage was not above the threshold.             |
+//! | 10        | The tasks ran and the usage is below it now.       |
+//! | 11        | The tasks ran and the usage is still above it.     |
+//!
+//! A dry run above the threshold only prints the plans of the tasks and exits with 0, since
+//! nothing was freed that could bring the usage down.
+//! Errors exit with 1, and invalid arguments with 2, as for every other command.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{report::Report, space::Usage};
+
+/// The tasks run when none are configured.
+pub const DEFAULT_TASKS: [&str; 3] = ["clean-package-cache", "clean-up-log-files", "clean-temp-files"];
+
+/// What an `auto` run did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// The usage was not above the threshold, so nothing ran.
+    NothingToDo,
+    /// The tasks ran and brought the usage down to the threshold or below.
+    Cleaned,
+    /// The tasks ran, but the usage is still above the threshold.
+    StillAbove,
+    /// The usage was above the threshold, and the tasks printed their plans in dry-run mode.
+    DryRun,
+}
+
+impl Status {
+    /// Returns the exit code of the program for this status.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Status::NothingToDo | Status::DryRun => 0,
+            Status::Cleaned => 10,
+            Status::StillAbove => 11,
+        }
+    }
+}
+
+/// The outcome of an `auto` run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outcome {
+    /// The path whose filesystem was checked.
+    pub mount: PathBuf,
+    /// The usage above which the tasks run, in percent.
+    pub threshold: f64,
+    /// The usage before the tasks ran, in percent.
+    pub used_before: f64,
+    /// The usage after the tasks ran, in percent. In dry-run mode, this is the usage before.
+    pub used_after: f64,
+    /// The IDs of the tasks that ran successfully.
+    pub tasks: Vec<String>,
+    /// The IDs of the tasks that failed.
+    pub failed: Vec<String>,
+    /// What the run did.
+    pub status: Status,
+}
+
+/// Parses a percentage such as `85%` or `85`.
+///
+/// # Errors
+///
+/// Returns an error if `percent` is not a number from 0 to 100.
+pub fn parse_percent(percent: &str) -> Result<f64> {
+    match percent.trim().trim_end_matches('%').parse::<f64>() {
+        Ok(number) if (0.0..=100.0).contains(&number) => Ok(number),
+        _ => Err(anyhow!("Invalid percentage: {percent}. Use a number from 0 to 100, e.g. `85%`.")),
+    }
+}
+
+/// Returns the usage of a filesystem in percent, computed like the `Use%` column of `df`: the
+/// blocks reserved for the superuser count as neither used nor available.
+pub fn used_percent(usage: Usage) -> f64 {
+    let used = usage.total.saturating_sub(usage.free);
+    match used + usage.available {
+        0 => 0.0,
+        size => used as f64 * 100.0 / size as f64,
+    }
+}
+
+/// Returns the exit code for the `auto` run recorded last in `report`, or `None` if it failed
+/// before deciding.
+pub fn exit_code(report: &Report) -> Option<i32> {
+    let details = report.tasks.last()?.details.as_ref()?;
+    let status: Status = serde_json::from_value(details.get("status")?.clone()).ok()?;
+    Some(status.exit_code())
+}
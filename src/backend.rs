@@ -0,0 +1,659 @@
+//! Package manager backends.
+//!
+//! Every distribution family ships its own package manager, each with its own spelling of "remove
+//! these packages" or "drop the download cache". The `PackageManager` trait captures the handful of
+//! operations the disk space optimizer needs, and the `Backend` enum selects an implementation
+//! either from `/etc/os-release` or from the `--backend` command-line flag.
+
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+
+use super::{execute_cmd, history, plan::Step, runner::CommandRunner};
+
+/// The file used to detect the running distribution.
+const OS_RELEASE: &str = "/etc/os-release";
+
+/// Kernel packages known to `pacman`. Arch Linux installs kernels as differently named packages
+/// rather than as multiple versions of the same package.
+const PACMAN_KERNELS: [&str; 6] = ["linux", "linux-lts", "linux-zen", "linux-hardened", "linux-rt", "linux-rt-lts"];
+
+/// The `rpm` query format of `parse_rpm_packages`. Version and release are joined the way other
+/// tools print them.
+pub const RPM_QUERY_FORMAT: &str = "%{NAME}\\t%{ARCH}\\t%{VERSION}-%{RELEASE}\\t%{SIZE}\\t%{INSTALLTIME}\\n";
+
+/// The `dpkg-query` format of `parse_dpkg_packages`.
+const DPKG_QUERY_FORMAT: &str =
+    "-f=${db:Status-Status}\\t${Package}\\t${Architecture}\\t${Version}\\t${Installed-Size}\\t${db-fsys:Last-Modified}\\n";
+
+/// An installed package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Package {
+    /// The name of the package, e.g. `firefox`.
+    pub name: String,
+    /// The architecture, e.g. `x86_64` or `noarch`.
+    pub arch: String,
+    /// The installed version, including the release.
+    pub version: String,
+    /// The space taken up by the installed files, in bytes.
+    pub size: u64,
+    /// The time the package was installed or last upgraded, if known.
+    pub installed: Option<SystemTime>,
+    /// The repository the package was installed from, if known.
+    pub repo: Option<String>,
+}
+
+/// The supported package manager backends.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Fedora, RHEL and derivatives.
+    Dnf,
+    /// Debian, Ubuntu and derivatives.
+    Apt,
+    /// Arch Linux and derivatives.
+    Pacman,
+    /// openSUSE and SUSE Linux Enterprise.
+    Zypper,
+}
+
+impl Backend {
+    /// Detects the backend of the running system by reading `/etc/os-release`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or if the distribution is not recognized.
+    pub fn detect() -> Result<Self> {
+        let contents = fs::read_to_string(OS_RELEASE).with_context(|| format!("Failed to read {OS_RELEASE}"))?;
+
+        Self::from_os_release(&contents)
+            .ok_or_else(|| anyhow!("Unsupported distribution in {OS_RELEASE}. Pass --backend to choose one."))
+    }
+
+    /// Picks a backend from the contents of an `os-release` file.
+    ///
+    /// The `ID` field is tried first, followed by each entry of `ID_LIKE`, so that derivatives such
+    /// as Linux Mint or Manjaro resolve to the backend of their parent distribution.
+    pub fn from_os_release(contents: &str) -> Option<Self> {
+        let field = |key: &str| -> Option<String> {
+            contents.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?.strip_prefix('=')?;
+                Some(value.trim().trim_matches('"').trim_matches('\'').to_lowercase())
+            })
+        };
+
+        let id = field("ID").unwrap_or_default();
+        let id_like = field("ID_LIKE").unwrap_or_default();
+
+        std::iter::once(id.as_str()).chain(id_like.split_whitespace()).find_map(|id| match id {
+            "fedora" | "rhel" | "centos" | "rocky" | "almalinux" | "nobara" | "ol" => Some(Backend::Dnf),
+            "debian" | "ubuntu" | "linuxmint" | "pop" | "raspbian" => Some(Backend::Apt),
+            "arch" | "manjaro" | "endeavouros" | "garuda" => Some(Backend::Pacman),
+            "suse" | "opensuse" | "sles" => Some(Backend::Zypper),
+            id if id.starts_with("opensuse") => Some(Backend::Zypper),
+            _ => None,
+        })
+    }
+
+    /// Returns the package manager implementation for this backend.
+    pub fn package_manager(self) -> Box<dyn PackageManager> {
+        match self {
+            Backend::Dnf => Box::new(Dnf),
+            Backend::Apt => Box::new(Apt),
+            Backend::Pacman => Box::new(Pacman),
+            Backend::Zypper => Box::new(Zypper),
+        }
+    }
+}
+
+/// The operations the disk space optimizer needs from a package manager.
+///
+/// Queries run immediately and return their results. Operations that change the system only build
+/// a `Step`, which is executed by a `Plan` or printed in dry-run mode. Steps never prompt, because
+/// the disk space optimizer asks for confirmation itself before running them.
+pub trait PackageManager: Sync {
+    /// Returns the name of the backend, e.g. `dnf`.
+    fn name(&self) -> &'static str;
+
+    /// Lists all installed packages with their metadata.
+    fn list_installed(&self, runner: &dyn CommandRunner) -> Result<Vec<Package>>;
+
+    /// Lists the installed kernel packages.
+    fn list_kernels(&self, runner: &dyn CommandRunner) -> Result<Vec<String>>;
+
+    /// Returns `true` if the kernel `package` provides the kernel `release`, as printed by
+    /// `uname -r`.
+    ///
+    /// By default the package name must end with the release, as in `kernel-6.5.6-300.fc39.x86_64`
+    /// or `linux-image-6.1.0-13-amd64`.
+    fn is_kernel_release(&self, package: &str, release: &str) -> bool {
+        package.strip_suffix(release).is_some_and(|prefix| prefix.ends_with('-'))
+    }
+
+    /// Builds the step that removes the given packages.
+    fn remove(&self, packages: &[&str]) -> Step;
+
+    /// Builds the step that installs the given packages.
+    fn install(&self, packages: &[&str]) -> Step;
+
+    /// Returns the ID of the latest transaction, if the package manager keeps a history of them.
+    fn last_transaction(&self, _runner: &dyn CommandRunner) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Builds the step that reverts a removal of `packages`, recorded with the ID `transaction`.
+    ///
+    /// By default the packages are installed again.
+    fn undo(&self, _transaction: Option<&str>, packages: &[&str]) -> Step {
+        self.install(packages)
+    }
+
+    /// Returns the directory of the package cache, e.g. `/var/cache/dnf`.
+    fn cache_dir(&self) -> &'static str;
+
+    /// Builds the step that cleans the package cache.
+    fn clean_cache(&self) -> Step;
+
+    /// Lists the packages which were installed as dependencies and are no longer required, as
+    /// `autoremove` would remove them.
+    fn unneeded(&self, runner: &dyn CommandRunner) -> Result<Vec<String>>;
+
+    /// Builds the step that removes packages which were installed as dependencies and are no
+    /// longer required. Returns `None` when there is nothing to remove.
+    fn autoremove(&self, runner: &dyn CommandRunner) -> Result<Option<Step>>;
+}
+
+/// Fills in the repositories of `packages` from `repos`, keyed by name and architecture.
+fn with_repos(mut packages: Vec<Package>, repos: &HashMap<(String, String), String>) -> Vec<Package> {
+    for package in &mut packages {
+        package.repo = repos.get(&(package.name.clone(), package.arch.clone())).cloned();
+    }
+    packages
+}
+
+/// Splits command output into trimmed, non-empty lines.
+fn lines(output: &str) -> Vec<String> {
+    output.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect()
+}
+
+/// Builds an argument list from a fixed prefix followed by package names.
+fn with_packages<'a>(prefix: &[&'a str], packages: &[&'a str]) -> Vec<&'a str> {
+    prefix.iter().chain(packages).copied().collect()
+}
+
+/// The `dnf` backend used on Fedora and RHEL.
+#[derive(Debug, Clone, Copy)]
+pub struct Dnf;
+
+impl PackageManager for Dnf {
+    fn name(&self) -> &'static str {
+        "dnf"
+    }
+
+    fn list_installed(&self, runner: &dyn CommandRunner) -> Result<Vec<Package>> {
+        let packages = parse_rpm_packages(&execute_cmd(runner, "rpm", &["-qa", "--queryformat", RPM_QUERY_FORMAT])?);
+        // The repository is only known to dnf, not to rpm.
+        let repos = parse_dnf_installed(&execute_cmd(runner, "dnf", &["list", "--installed"])?);
+        Ok(with_repos(packages, &repos))
+    }
+
+    fn list_kernels(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        Ok(lines(&execute_cmd(runner, "rpm", &["-q", "kernel"])?))
+    }
+
+    fn remove(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["dnf", "remove", "--assumeyes"], packages))
+            .with_preview(&with_packages(&["dnf", "remove", "--assumeno"], packages))
+    }
+
+    fn install(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["dnf", "install", "--assumeyes"], packages))
+            .with_preview(&with_packages(&["dnf", "install", "--assumeno"], packages))
+    }
+
+    fn last_transaction(&self, runner: &dyn CommandRunner) -> Result<Option<String>> {
+        Ok(parse_dnf_history(&execute_cmd(runner, "dnf", &["history", "list"])?))
+    }
+
+    fn undo(&self, transaction: Option<&str>, packages: &[&str]) -> Step {
+        match transaction {
+            Some(transaction) => Step::sudo(&["dnf", "history", "undo", "--assumeyes", transaction]).with_preview(&[
+                "dnf",
+                "history",
+                "info",
+                transaction,
+            ]),
+            None => self.install(packages),
+        }
+    }
+
+    fn cache_dir(&self) -> &'static str {
+        "/var/cache/dnf"
+    }
+
+    fn clean_cache(&self) -> Step {
+        Step::sudo(&["dnf", "clean", "all"]).with_preview(&["du", "-sh", self.cache_dir()])
+    }
+
+    fn unneeded(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        Ok(lines(&execute_cmd(runner, "dnf", &["repoquery", "--unneeded", "--queryformat", "%{name}\\n"])?))
+    }
+
+    fn autoremove(&self, _runner: &dyn CommandRunner) -> Result<Option<Step>> {
+        Ok(Some(Step::sudo(&["dnf", "autoremove", "--assumeyes"]).with_preview(&["dnf", "autoremove", "--assumeno"])))
+    }
+}
+
+/// The `apt` backend used on Debian and Ubuntu.
+#[derive(Debug, Clone, Copy)]
+pub struct Apt;
+
+impl PackageManager for Apt {
+    fn name(&self) -> &'static str {
+        "apt"
+    }
+
+    fn list_installed(&self, runner: &dyn CommandRunner) -> Result<Vec<Package>> {
+        let packages = parse_dpkg_packages(&execute_cmd(runner, "dpkg-query", &["-W", DPKG_QUERY_FORMAT])?);
+        let repos = parse_apt_installed(&execute_cmd(runner, "apt", &["list", "--installed"])?);
+        Ok(with_repos(packages, &repos))
+    }
+
+    fn list_kernels(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        Ok(lines(&execute_cmd(runner, "dpkg-query", &["-W", "-f=${Package}\\n", "linux-image-[0-9]*"])?))
+    }
+
+    fn remove(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["apt-get", "remove", "--assume-yes"], packages))
+            .with_preview(&with_packages(&["apt-get", "--simulate", "remove"], packages))
+    }
+
+    fn install(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["apt-get", "install", "--assume-yes"], packages))
+            .with_preview(&with_packages(&["apt-get", "--simulate", "install"], packages))
+    }
+
+    fn cache_dir(&self) -> &'static str {
+        "/var/cache/apt/archives"
+    }
+
+    fn clean_cache(&self) -> Step {
+        Step::sudo(&["apt-get", "clean"]).with_preview(&["du", "-sh", self.cache_dir()])
+    }
+
+    fn unneeded(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        Ok(parse_apt_simulated_removals(&execute_cmd(runner, "apt-get", &["--simulate", "autoremove"])?))
+    }
+
+    fn autoremove(&self, _runner: &dyn CommandRunner) -> Result<Option<Step>> {
+        Ok(Some(Step::sudo(&["apt-get", "autoremove", "--assume-yes"]).with_preview(&[
+            "apt-get",
+            "--simulate",
+            "autoremove",
+        ])))
+    }
+}
+
+/// The `pacman` backend used on Arch Linux.
+#[derive(Debug, Clone, Copy)]
+pub struct Pacman;
+
+impl PackageManager for Pacman {
+    fn name(&self) -> &'static str {
+        "pacman"
+    }
+
+    fn list_installed(&self, runner: &dyn CommandRunner) -> Result<Vec<Package>> {
+        // The C locale makes the install dates parseable.
+        let packages = parse_pacman_info(&execute_cmd(runner, "env", &["LC_ALL=C", "pacman", "-Qi"])?);
+        let repos = parse_pacman_sync_list(&execute_cmd(runner, "pacman", &["-Sl"])?);
+        Ok(with_repos(packages, &repos))
+    }
+
+    fn list_kernels(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        let installed = lines(&execute_cmd(runner, "pacman", &["-Qq"])?);
+        Ok(installed.into_iter().filter(|pkg| PACMAN_KERNELS.contains(&pkg.as_str())).collect())
+    }
+
+    fn is_kernel_release(&self, package: &str, release: &str) -> bool {
+        // Releases end with the flavour of the kernel package, e.g. `6.6.30-1-lts` for
+        // `linux-lts`, while the plain `linux` package has none, as in `6.9.1-arch1-1`.
+        let flavour = PACMAN_KERNELS[1..]
+            .iter()
+            .filter(|kernel| release.ends_with(&kernel["linux".len()..]))
+            .max_by_key(|kernel| kernel.len());
+        package == *flavour.unwrap_or(&PACMAN_KERNELS[0])
+    }
+
+    fn remove(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["pacman", "-Rns", "--noconfirm"], packages))
+            .with_preview(&with_packages(&["pacman", "-Rns", "--print"], packages))
+    }
+
+    fn install(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["pacman", "-S", "--needed", "--noconfirm"], packages))
+            .with_preview(&with_packages(&["pacman", "-S", "--print"], packages))
+    }
+
+    fn cache_dir(&self) -> &'static str {
+        "/var/cache/pacman/pkg"
+    }
+
+    fn clean_cache(&self) -> Step {
+        Step::sudo(&["pacman", "-Sc", "--noconfirm"]).with_preview(&["du", "-sh", self.cache_dir()])
+    }
+
+    fn unneeded(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        let output = runner.run("pacman", &["-Qdtq"], None).context("Failed to execute command: pacman -Qdtq")?;
+        match output.status {
+            Some(0) => Ok(lines(&output.stdout)),
+            // `pacman -Qdtq` exits with status 1 and prints nothing when there are no orphaned
+            // packages. Errors, e.g. with the database, print a message.
+            Some(1) if output.stdout.trim().is_empty() && output.stderr.trim().is_empty() => Ok(Vec::new()),
+            status => Err(anyhow!(
+                "Command failed with exit code {status}: {stderr}",
+                status = status.map_or_else(|| String::from("signal"), |code| code.to_string()),
+                stderr = output.stderr.trim()
+            )),
+        }
+    }
+
+    fn autoremove(&self, runner: &dyn CommandRunner) -> Result<Option<Step>> {
+        let orphans = self.unneeded(runner)?;
+        if orphans.is_empty() {
+            return Ok(None);
+        }
+        let orphans: Vec<&str> = orphans.iter().map(String::as_str).collect();
+        Ok(Some(self.remove(&orphans)))
+    }
+}
+
+/// The `zypper` backend used on openSUSE.
+#[derive(Debug, Clone, Copy)]
+pub struct Zypper;
+
+impl PackageManager for Zypper {
+    fn name(&self) -> &'static str {
+        "zypper"
+    }
+
+    fn list_installed(&self, runner: &dyn CommandRunner) -> Result<Vec<Package>> {
+        let packages = parse_rpm_packages(&execute_cmd(runner, "rpm", &["-qa", "--queryformat", RPM_QUERY_FORMAT])?);
+        let repos =
+            parse_zypper_installed(&execute_cmd(runner, "zypper", &["--quiet", "packages", "--installed-only"])?);
+        Ok(with_repos(packages, &repos))
+    }
+
+    fn list_kernels(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        Ok(lines(&execute_cmd(runner, "rpm", &["-q", "kernel-default"])?))
+    }
+
+    fn is_kernel_release(&self, package: &str, release: &str) -> bool {
+        // A release such as `6.4.0-150600.23.7-default` is provided by the package
+        // `kernel-default-6.4.0-150600.23.7.1.x86_64`.
+        let Some((version, flavour)) = release.rsplit_once('-') else {
+            return false;
+        };
+        package
+            .strip_prefix(&format!("kernel-{flavour}-{version}"))
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    }
+
+    fn remove(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["zypper", "--non-interactive", "remove", "--clean-deps"], packages)).with_preview(
+            &with_packages(&["zypper", "--non-interactive", "remove", "--dry-run", "--clean-deps"], packages),
+        )
+    }
+
+    fn install(&self, packages: &[&str]) -> Step {
+        Step::sudo(&with_packages(&["zypper", "--non-interactive", "install"], packages))
+            .with_preview(&with_packages(&["zypper", "--non-interactive", "install", "--dry-run"], packages))
+    }
+
+    fn cache_dir(&self) -> &'static str {
+        "/var/cache/zypp"
+    }
+
+    fn clean_cache(&self) -> Step {
+        Step::sudo(&["zypper", "--non-interactive", "clean", "--all"]).with_preview(&["du", "-sh", self.cache_dir()])
+    }
+
+    fn unneeded(&self, runner: &dyn CommandRunner) -> Result<Vec<String>> {
+        Ok(parse_zypper_unneeded(&execute_cmd(runner, "zypper", &["--quiet", "packages", "--unneeded"])?))
+    }
+
+    fn autoremove(&self, runner: &dyn CommandRunner) -> Result<Option<Step>> {
+        let unneeded = self.unneeded(runner)?;
+        if unneeded.is_empty() {
+            return Ok(None);
+        }
+        let unneeded: Vec<&str> = unneeded.iter().map(String::as_str).collect();
+        Ok(Some(self.remove(&unneeded)))
+    }
+}
+
+/// Extracts the ID of the latest transaction from the table printed by `dnf history list`, which
+/// lists the newest transaction first.
+///
+/// Rows look like `42 | remove foo | 2023-05-01 10:00 | Removed | 1` with dnf 4, and like
+/// `42 dnf remove foo 2023-05-01 10:00:00 1` with dnf 5; the header and separator lines are skipped.
+pub fn parse_dnf_history(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let id = line.split(['|', ' ', '\t']).find(|column| !column.is_empty())?;
+        id.bytes().all(|c| c.is_ascii_digit()).then(|| id.to_string())
+    })
+}
+
+/// Parses the packages printed by `rpm -qa` with `RPM_QUERY_FORMAT`, one per line.
+///
+/// The `gpg-pubkey` entries holding the imported signing keys are not packages and are skipped.
+pub fn parse_rpm_packages(output: &str) -> Vec<Package> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let [name, arch, version, size, installed] = line.trim().split('\t').collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            (name != "gpg-pubkey").then(|| Package {
+                name: name.to_string(),
+                arch: arch.to_string(),
+                version: version.to_string(),
+                size: size.parse().unwrap_or_default(),
+                installed: epoch_secs(installed),
+                repo: None,
+            })
+        })
+        .collect()
+}
+
+/// Extracts the repository of each package, keyed by name and architecture, from the table
+/// printed by `dnf list --installed`.
+///
+/// Rows look like `firefox.x86_64   115.0-1.fc38   @updates`, where long names push the other
+/// columns onto the next line. Header lines such as `Installed Packages` are skipped.
+pub fn parse_dnf_installed(output: &str) -> HashMap<(String, String), String> {
+    let mut repos = HashMap::new();
+    let mut columns: Vec<&str> = Vec::new();
+    for line in output.lines() {
+        let continued = line.starts_with(char::is_whitespace);
+        if !continued && !line.split_whitespace().next().is_some_and(|name| name.contains('.')) {
+            columns.clear();
+            continue;
+        }
+        columns.extend(line.split_whitespace());
+        if let [name_arch, _version, repo] = columns[..] {
+            if let Some((name, arch)) = name_arch.rsplit_once('.') {
+                repos.insert((name.to_string(), arch.to_string()), repo.trim_start_matches('@').to_string());
+            }
+            columns.clear();
+        }
+    }
+    repos
+}
+
+/// Parses the packages printed by `dpkg-query -W` with `DPKG_QUERY_FORMAT`, one per line.
+///
+/// Packages that were removed but left their configuration files behind are skipped. dpkg does not
+/// record install dates, so the time the file list of the package was last written is used.
+pub fn parse_dpkg_packages(output: &str) -> Vec<Package> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let [status, name, arch, version, size, modified] = line.trim().split('\t').collect::<Vec<_>>()[..] else {
+                return None;
+            };
+            (status == "installed").then(|| Package {
+                name: name.to_string(),
+                arch: arch.to_string(),
+                version: version.to_string(),
+                // The installed size is in KiB.
+                size: size.parse::<u64>().unwrap_or_default() * 1024,
+                installed: epoch_secs(modified),
+                repo: None,
+            })
+        })
+        .collect()
+}
+
+/// Extracts the archive each package was installed from, keyed by name and architecture, from the
+/// list printed by `apt list --installed`.
+///
+/// Lines look like `firefox/jammy-updates,now 115.0 amd64 [installed]`. Packages only known as
+/// `now` are not available from any configured archive and are left out.
+pub fn parse_apt_installed(output: &str) -> HashMap<(String, String), String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let (name, archives) = columns.next()?.split_once('/')?;
+            let arch = columns.nth(1)?;
+            let archive = archives.split(',').find(|archive| *archive != "now")?;
+            Some(((name.to_string(), arch.to_string()), archive.to_string()))
+        })
+        .collect()
+}
+
+/// Parses the packages printed by `pacman -Qi` in the C locale, one block of `Field : value`
+/// lines per package.
+pub fn parse_pacman_info(output: &str) -> Vec<Package> {
+    output
+        .split("\n\n")
+        .filter_map(|block| {
+            let fields: HashMap<&str, &str> = block
+                .lines()
+                .filter_map(|line| line.split_once(" : "))
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .collect();
+            Some(Package {
+                name: fields.get("Name")?.to_string(),
+                arch: fields.get("Architecture")?.to_string(),
+                version: fields.get("Version")?.to_string(),
+                size: fields.get("Installed Size").and_then(|size| parse_pacman_size(size)).unwrap_or_default(),
+                installed: fields.get("Install Date").and_then(|date| parse_pacman_date(date)),
+                repo: None,
+            })
+        })
+        .collect()
+}
+
+/// Extracts the repository of each installed package, keyed by name and architecture, from the
+/// list printed by `pacman -Sl`.
+///
+/// Lines look like `extra firefox 115.0-1 [installed]`, and like `extra firefox 115.0-1
+/// [installed: 114.0-1]` when the installed version differs. The list has no architectures, so
+/// each package is keyed by both the architecture of the machine and `any`.
+pub fn parse_pacman_sync_list(output: &str) -> HashMap<(String, String), String> {
+    let mut repos = HashMap::new();
+    for line in output.lines().filter(|line| line.contains("[installed")) {
+        let mut columns = line.split_whitespace();
+        let (Some(repo), Some(name)) = (columns.next(), columns.next()) else {
+            continue;
+        };
+        for arch in [std::env::consts::ARCH, "any"] {
+            repos.insert((name.to_string(), arch.to_string()), repo.to_string());
+        }
+    }
+    repos
+}
+
+/// Parses a size printed by pacman, such as `1.50 MiB`.
+fn parse_pacman_size(size: &str) -> Option<u64> {
+    let (number, unit) = size.split_once(' ')?;
+    let exponent = ["B", "KiB", "MiB", "GiB", "TiB"].iter().position(|candidate| *candidate == unit)?;
+    let number: f64 = number.parse().ok()?;
+    Some((number * 1024f64.powi(exponent as i32)) as u64)
+}
+
+/// Parses a date printed by pacman in the C locale, such as `Mon May  1 10:00:00 2023`. The time
+/// is taken as UTC, which is close enough to order packages by.
+fn parse_pacman_date(date: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let [_weekday, month, day, time, year] = date.split_whitespace().collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let days = history::days_from_civil(year.parse().ok()?, month, day.parse().ok()?);
+    let secs = time.split(':').try_fold(0, |secs, part| part.parse::<i64>().ok().map(|part| secs * 60 + part))?;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(u64::try_from(days * 86_400 + secs).ok()?))
+}
+
+/// Parses a number of seconds since the epoch, as printed by rpm and dpkg.
+fn epoch_secs(secs: &str) -> Option<SystemTime> {
+    secs.parse().ok().map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Extracts the repository of each installed package, keyed by name and architecture, from the
+/// table printed by `zypper packages --installed-only`.
+///
+/// Rows look like `i | repo-oss | libfoo1 | 1.0-1.1 | x86_64`. Packages from no known repository
+/// are listed as `@System` and left out.
+pub fn parse_zypper_installed(output: &str) -> HashMap<(String, String), String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('|').map(str::trim).collect();
+            match columns.as_slice() {
+                [status, repo, name, _version, arch, ..] if status.starts_with('i') && *repo != "@System" => {
+                    Some(((name.to_string(), arch.to_string()), repo.to_string()))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Extracts the names of the packages `apt-get --simulate autoremove` would remove.
+///
+/// Removals are printed as `Remv libfoo1 [1.0-1]`, with an architecture suffix such as
+/// `libfoo1:i386` for foreign packages, which is dropped.
+pub fn parse_apt_simulated_removals(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("Remv ")?.split_whitespace().next())
+        .map(|name| name.split_once(':').map_or(name, |(name, _)| name).to_string())
+        .collect()
+}
+
+/// Extracts package names from the table printed by `zypper packages --unneeded`.
+///
+/// Rows look like `i | repo-oss | libfoo1 | 1.0-1.1 | x86_64`; the header and separator lines are
+/// skipped.
+pub fn parse_zypper_unneeded(output: &str) -> Vec<String> {
+    let mut names: Vec<String> = output
+        .lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('|').map(str::trim).collect();
+            match columns.as_slice() {
+                [status, _repo, name, ..] if status.starts_with('i') => Some(name.to_string()),
+                _ => None,
+            }
+        })
+        .collect();
+    names.dedup();
+    names
+}
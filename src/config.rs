@@ -0,0 +1,206 @@
+//! Configuration files.
+//!
+//! Defaults for each task are read from `/etc/disk-space-optimizer.toml` and then from
+//! `$XDG_CONFIG_HOME/disk-space-optimizer/config.toml` (usually `~/.config`), with values in the
+//! user file taking precedence. Command-line flags in turn override both files. Every key is
+//! optional:
+//!
+//! ```toml
+//! [journal]
+//! vacuum_time = 14       # days
+//! vacuum_size = "500M"
+//! vacuum_files = 10
+//!
+//! [logs]
+//! max_age = 30           # days
+//! max_size = "1G"
+//! compress = true
+//! unattended = false
+//!
+//! [kernels]
+//! keep = 3
+//!
+//! [packages]
+//! protected = ["firefox", "vim-enhanced"]
+//!
+//! [tasks]
+//! enabled = ["clean-package-cache", "clean-up-log-files"]
+//!
+//! [quarantine]
+//! enabled = true
+//!
+//! [auto]
+//! tasks = ["clean-package-cache", "clean-up-log-files", "clean-temp-files"]
+//! ```
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// The system-wide configuration file.
+pub const SYSTEM_CONFIG: &str = "/etc/disk-space-optimizer.toml";
+
+/// The typed contents of a configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Retention of the systemd journal, used by `clean-up-log-files`.
+    pub journal: JournalConfig,
+    /// Retention of rotated logs below `/var/log`, used by `clean-up-log-files`.
+    pub logs: LogConfig,
+    /// Retention of installed kernels, used by `remove-old-kernels`.
+    pub kernels: KernelConfig,
+    /// Packages that must never be removed.
+    pub packages: PackageConfig,
+    /// The tasks offered in the interactive menu.
+    pub tasks: TaskConfig,
+    /// Whether deleted files are kept in the quarantine.
+    pub quarantine: QuarantineConfig,
+    /// The tasks run by `auto`.
+    pub auto: AutoConfig,
+}
+
+/// The `[journal]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JournalConfig {
+    /// Remove journal entries older than this many days.
+    pub vacuum_time: Option<u32>,
+    /// Shrink the journal to this size, e.g. `500M`, as understood by `journalctl --vacuum-size`.
+    pub vacuum_size: Option<String>,
+    /// Keep at most this many archived journal files.
+    pub vacuum_files: Option<u32>,
+}
+
+/// The `[logs]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// The directory searched for rotated logs, `/var/log` by default.
+    pub directory: Option<PathBuf>,
+    /// Delete rotated logs older than this many days.
+    pub max_age: Option<u64>,
+    /// Delete the oldest rotated logs until the remaining ones fit into this size, e.g. `1G`.
+    pub max_size: Option<String>,
+    /// Compress the remaining rotated logs. Enabled by default.
+    pub compress: Option<bool>,
+    /// Also delete and compress rotated logs when `clean-up-log-files` runs unattended, in `auto`
+    /// and `reclaim`. Disabled by default, so that only the journal is vacuumed there.
+    pub unattended: Option<bool>,
+}
+
+/// The `[kernels]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KernelConfig {
+    /// The number of newest kernels to keep.
+    pub keep: Option<usize>,
+}
+
+/// The `[packages]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackageConfig {
+    /// Package names that are dropped from every removal plan.
+    pub protected: Vec<String>,
+}
+
+/// The `[tasks]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskConfig {
+    /// The command names shown in the menu, e.g. `clean-package-cache`. All tasks are shown when
+    /// unset. Commands given on the command line always run.
+    pub enabled: Option<Vec<String>>,
+}
+
+/// The `[auto]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoConfig {
+    /// The low-risk tasks `auto` runs, e.g. `clean-package-cache`. `auto::DEFAULT_TASKS` when
+    /// unset.
+    pub tasks: Option<Vec<String>>,
+}
+
+/// The `[quarantine]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuarantineConfig {
+    /// Move files into the quarantine instead of deleting them, as with `--quarantine`.
+    pub enabled: Option<bool>,
+}
+
+impl Config {
+    /// Loads and merges the system-wide and the user configuration files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configuration file exists but cannot be read or parsed.
+    pub fn load() -> Result<Self> {
+        let mut paths = vec![PathBuf::from(SYSTEM_CONFIG)];
+        paths.extend(user_config_path());
+        Self::load_from(&paths)
+    }
+
+    /// Loads and merges the given files in order, so that later files take precedence. Files that
+    /// do not exist are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file exists but cannot be read or parsed.
+    pub fn load_from(paths: &[PathBuf]) -> Result<Self> {
+        let mut config = Self::default();
+        for path in paths {
+            let contents = match fs::read_to_string(path) {
+                Ok(contents) => contents,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path.display())),
+            };
+            let file: Config =
+                toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+            config = config.merge(file);
+        }
+        Ok(config)
+    }
+
+    /// Overrides the values of this configuration with those set in `other`. Protected packages
+    /// from both are kept.
+    pub fn merge(mut self, other: Config) -> Self {
+        self.journal.vacuum_time = other.journal.vacuum_time.or(self.journal.vacuum_time);
+        self.journal.vacuum_size = other.journal.vacuum_size.or(self.journal.vacuum_size);
+        self.journal.vacuum_files = other.journal.vacuum_files.or(self.journal.vacuum_files);
+        self.logs.directory = other.logs.directory.or(self.logs.directory);
+        self.logs.max_age = other.logs.max_age.or(self.logs.max_age);
+        self.logs.max_size = other.logs.max_size.or(self.logs.max_size);
+        self.logs.compress = other.logs.compress.or(self.logs.compress);
+        self.kernels.keep = other.kernels.keep.or(self.kernels.keep);
+        for package in other.packages.protected {
+            if !self.packages.protected.contains(&package) {
+                self.packages.protected.push(package);
+            }
+        }
+        self.tasks.enabled = other.tasks.enabled.or(self.tasks.enabled);
+        self.quarantine.enabled = other.quarantine.enabled.or(self.quarantine.enabled);
+        self.auto.tasks = other.auto.tasks.or(self.auto.tasks);
+        self
+    }
+
+    /// Returns `true` if the command named `name` is offered in the menu.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.tasks.enabled.as_ref().is_none_or(|enabled| enabled.iter().any(|task| task == name))
+    }
+}
+
+/// Returns the path of the user configuration file, if the home directory is known.
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("disk-space-optimizer/config.toml"))
+}
@@ -0,0 +1,212 @@
+//! Build caches of developer tools.
+//!
+//! Package managers of programming languages keep every downloaded dependency and build artifact
+//! in caches below the home directory, and projects carry their own `target/` and `node_modules`
+//! directories. All of them are recreated on the next build, so they can be removed when space is
+//! short. The locations honour the environment variables each tool reads, e.g. `CARGO_HOME` or
+//! `GOMODCACHE`.
+
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use super::{
+    analyze::{self, ScanOptions},
+    targets,
+};
+
+/// A tool whose caches can be removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tool {
+    /// The name used on the command line, e.g. `cargo`.
+    pub name: &'static str,
+    /// A description of what is cached, as shown in the selection.
+    pub label: &'static str,
+}
+
+/// Downloaded crates and git checkouts of Cargo.
+pub const CARGO: Tool = Tool { name: "cargo", label: "Cargo registry and git checkouts" };
+/// `target/` directories of Cargo projects.
+pub const CARGO_TARGETS: Tool = Tool { name: "cargo-targets", label: "Stale Cargo target directories" };
+/// `node_modules` directories of JavaScript projects.
+pub const NODE_MODULES: Tool = Tool { name: "node-modules", label: "Stale node_modules directories" };
+/// The npm package cache.
+pub const NPM: Tool = Tool { name: "npm", label: "npm cache" };
+/// The Yarn package caches, of Yarn 1 and of Yarn 2 and later.
+pub const YARN: Tool = Tool { name: "yarn", label: "Yarn cache" };
+/// The pnpm content-addressable store.
+pub const PNPM: Tool = Tool { name: "pnpm", label: "pnpm store" };
+/// The pip wheel and HTTP cache.
+pub const PIP: Tool = Tool { name: "pip", label: "pip cache" };
+/// The uv cache.
+pub const UV: Tool = Tool { name: "uv", label: "uv cache" };
+/// The Go build cache and module cache.
+pub const GO: Tool = Tool { name: "go", label: "Go build and module cache" };
+/// The Gradle dependency and build caches.
+pub const GRADLE: Tool = Tool { name: "gradle", label: "Gradle caches" };
+/// The local Maven repository.
+pub const MAVEN: Tool = Tool { name: "maven", label: "Maven repository" };
+
+/// Every tool, in the order they are listed.
+pub const TOOLS: [Tool; 11] = [CARGO, CARGO_TARGETS, NODE_MODULES, NPM, YARN, PNPM, PIP, UV, GO, GRADLE, MAVEN];
+
+/// The tools whose caches are directories inside projects, which are searched for below the
+/// project roots.
+pub const PROJECT_TOOLS: [Tool; 2] = [CARGO_TARGETS, NODE_MODULES];
+
+/// Project directories not modified for this many days are considered stale unless configured
+/// otherwise.
+pub const DEFAULT_STALE_DAYS: u64 = 30;
+
+/// A directory holding cached data of a tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    /// The tool owning the cache.
+    pub tool: Tool,
+    /// The cache directory.
+    pub path: PathBuf,
+    /// Whether the tool makes the cached files read-only, as Go does for its module cache, so that
+    /// they must be made writable before they can be removed.
+    pub read_only: bool,
+}
+
+impl Cache {
+    fn new(tool: Tool, path: PathBuf) -> Self {
+        Self { tool, path, read_only: false }
+    }
+}
+
+/// The caches of a single tool and the space they take up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolCaches {
+    /// The name of the tool, e.g. `cargo`.
+    pub tool: &'static str,
+    /// A description of what is cached.
+    pub label: &'static str,
+    /// The existing cache directories.
+    pub paths: Vec<PathBuf>,
+    /// The directories whose files must be made writable before removal.
+    #[serde(skip)]
+    pub read_only: Vec<PathBuf>,
+    /// The space allocated to all directories, in bytes.
+    pub bytes: u64,
+}
+
+/// Returns the cache locations of every tool, reading environment variables through `env`.
+///
+/// Locations that do not exist are included; `measure` skips them.
+pub fn known_caches(env: impl Fn(&str) -> Option<PathBuf>) -> Vec<Cache> {
+    let Some(home) = env("HOME") else {
+        return Vec::new();
+    };
+    // Relative paths in these variables are ignored, as the tools themselves mostly do.
+    let var = |name: &str| env(name).filter(|path| path.is_absolute());
+    let cache_home = var("XDG_CACHE_HOME").unwrap_or_else(|| home.join(".cache"));
+    let data_home = var("XDG_DATA_HOME").unwrap_or_else(|| home.join(".local/share"));
+
+    let cargo_home = var("CARGO_HOME").unwrap_or_else(|| home.join(".cargo"));
+    let go_path = var("GOPATH").unwrap_or_else(|| home.join("go"));
+    let gradle_home = var("GRADLE_USER_HOME").unwrap_or_else(|| home.join(".gradle"));
+
+    let mut caches = vec![
+        Cache::new(CARGO, cargo_home.join("registry")),
+        Cache::new(CARGO, cargo_home.join("git")),
+        Cache::new(NPM, var("npm_config_cache").unwrap_or_else(|| home.join(".npm")).join("_cacache")),
+        Cache::new(YARN, var("YARN_CACHE_FOLDER").unwrap_or_else(|| cache_home.join("yarn"))),
+        Cache::new(YARN, home.join(".yarn/berry/cache")),
+        Cache::new(PNPM, data_home.join("pnpm/store")),
+        Cache::new(PNPM, cache_home.join("pnpm")),
+        Cache::new(PIP, var("PIP_CACHE_DIR").unwrap_or_else(|| cache_home.join("pip"))),
+        Cache::new(UV, var("UV_CACHE_DIR").unwrap_or_else(|| cache_home.join("uv"))),
+        Cache::new(GO, var("GOCACHE").unwrap_or_else(|| cache_home.join("go-build"))),
+        Cache { read_only: true, ..Cache::new(GO, var("GOMODCACHE").unwrap_or_else(|| go_path.join("pkg/mod"))) },
+        Cache::new(GRADLE, gradle_home.join("caches")),
+        Cache::new(MAVEN, home.join(".m2/repository")),
+    ];
+    caches.dedup_by(|a, b| a.path == b.path);
+    caches
+}
+
+/// Finds the `target/` directories of Cargo projects and the `node_modules` directories of
+/// JavaScript projects below `roots` that were not modified within `stale_after` before `now`.
+///
+/// Hidden directories are skipped, and found directories are not searched further.
+pub fn find_project_caches(roots: &[PathBuf], stale_after: Duration, now: SystemTime) -> Vec<Cache> {
+    let mut caches = Vec::new();
+    let mut pending: Vec<PathBuf> = roots.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                continue;
+            }
+            let name = entry.file_name();
+            let path = entry.path();
+            let tool = match name.to_str() {
+                Some("target") if targets::is_target_dir(&path) => CARGO_TARGETS,
+                Some("node_modules") if dir.join("package.json").is_file() => NODE_MODULES,
+                Some(name) if name.starts_with('.') => continue,
+                _ => {
+                    pending.push(path);
+                    continue;
+                }
+            };
+            let age = now.duration_since(last_modified(&path)).unwrap_or_default();
+            if age > stale_after {
+                caches.push(Cache::new(tool, path));
+            }
+        }
+    }
+    caches.sort_by(|a, b| a.path.cmp(&b.path));
+    caches
+}
+
+/// Returns the latest modification time of `dir` and the entries directly inside it.
+fn last_modified(dir: &Path) -> SystemTime {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let entries = fs::read_dir(dir).into_iter().flatten().flatten();
+    entries.filter_map(|entry| modified(&entry.path())).chain(modified(dir)).max().unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Measures the existing `caches` and sums them per tool, heaviest first. Tools without any
+/// cached data are left out.
+pub fn measure(caches: &[Cache]) -> Vec<ToolCaches> {
+    let options = ScanOptions { one_file_system: false, top: 0 };
+    let mut tools: Vec<ToolCaches> = Vec::new();
+    for cache in caches {
+        let Ok(analysis) = analyze::scan(&cache.path, options) else {
+            continue;
+        };
+        if analysis.root.size.allocated == 0 {
+            continue;
+        }
+        let index = match tools.iter().position(|tool| tool.tool == cache.tool.name) {
+            Some(index) => index,
+            None => {
+                tools.push(ToolCaches {
+                    tool: cache.tool.name,
+                    label: cache.tool.label,
+                    paths: Vec::new(),
+                    read_only: Vec::new(),
+                    bytes: 0,
+                });
+                tools.len() - 1
+            }
+        };
+        let tool = &mut tools[index];
+        tool.paths.push(cache.path.clone());
+        if cache.read_only {
+            tool.read_only.push(cache.path.clone());
+        }
+        tool.bytes += analysis.root.size.allocated;
+    }
+    tools.sort_by_key(|tool| Reverse(tool.bytes));
+    tools
+}
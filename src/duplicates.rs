@@ -0,0 +1,159 @@
+//! Duplicate files.
+//!
+//! Files are compared in stages so that most of them are never read in full: first they are
+//! grouped by size, then files of equal size by a BLAKE3 hash of their first bytes, and only files
+//! that still match are hashed completely. Hard links to the same inode are counted as one file,
+//! since they take up the space only once.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    fs::{self, File},
+    io::{self, Read},
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+/// The number of leading bytes hashed to tell apart files of equal size.
+const PARTIAL_HASH_BYTES: u64 = 64 * 1024;
+
+/// The `statfs(2)` magic numbers of the filesystems that support reflinks.
+const REFLINK_FILESYSTEMS: [libc::c_long; 2] = [
+    0x9123_683E, // btrfs
+    0x5846_5342, // xfs
+];
+
+/// Files with identical contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateSet {
+    /// The size of each file, in bytes.
+    pub bytes: u64,
+    /// The BLAKE3 hash of the contents, in hex.
+    pub hash: String,
+    /// The paths of the files, sorted.
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateSet {
+    /// Returns the space that would be freed by keeping a single copy, in bytes.
+    pub fn wasted(&self) -> u64 {
+        self.bytes * (self.paths.len() as u64).saturating_sub(1)
+    }
+}
+
+/// The duplicate files found below a set of paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Duplicates {
+    /// The sets of identical files, most wasted space first.
+    pub sets: Vec<DuplicateSet>,
+    /// The space taken up by all copies beyond the first of each set, in bytes.
+    pub wasted: u64,
+    /// The number of files compared.
+    pub files: u64,
+    /// The number of files and directories that could not be read.
+    pub errors: u64,
+}
+
+/// A candidate file.
+struct Candidate {
+    path: PathBuf,
+    bytes: u64,
+}
+
+/// Finds the files below `paths` of at least `min_size` bytes that have identical contents.
+///
+/// Symbolic links are not followed, and empty files are never reported.
+pub fn find(paths: &[PathBuf], min_size: u64) -> Duplicates {
+    let mut duplicates = Duplicates::default();
+    let mut by_size: HashMap<u64, Vec<Candidate>> = HashMap::new();
+    let mut inodes = HashSet::new();
+
+    let mut pending: Vec<PathBuf> = paths.to_vec();
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            duplicates.errors += 1;
+            continue;
+        };
+        if metadata.is_dir() {
+            match fs::read_dir(&path) {
+                Ok(entries) => pending.extend(entries.flatten().map(|entry| entry.path())),
+                Err(_) => duplicates.errors += 1,
+            }
+            continue;
+        }
+        let bytes = metadata.len();
+        if !metadata.is_file() || bytes == 0 || bytes < min_size || !inodes.insert((metadata.dev(), metadata.ino())) {
+            continue;
+        }
+        duplicates.files += 1;
+        by_size.entry(bytes).or_default().push(Candidate { path, bytes });
+    }
+
+    for candidates in by_size.into_values().filter(|candidates| candidates.len() > 1) {
+        for candidates in group_by_hash(candidates, Some(PARTIAL_HASH_BYTES), &mut duplicates.errors) {
+            // The partial hash already covered all of a small file.
+            let groups = if candidates[0].1.bytes <= PARTIAL_HASH_BYTES {
+                vec![candidates]
+            } else {
+                let candidates = candidates.into_iter().map(|(_, candidate)| candidate).collect();
+                group_by_hash(candidates, None, &mut duplicates.errors)
+            };
+            for group in groups {
+                let mut paths: Vec<PathBuf> = group.iter().map(|(_, candidate)| candidate.path.clone()).collect();
+                paths.sort();
+                duplicates.sets.push(DuplicateSet { bytes: group[0].1.bytes, hash: group[0].0.clone(), paths });
+            }
+        }
+    }
+
+    duplicates.sets.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+    duplicates.wasted = duplicates.sets.iter().map(DuplicateSet::wasted).sum();
+    duplicates
+}
+
+/// Hashes the first `limit` bytes of each candidate, or all of it when `None`, and returns the
+/// groups of more than one candidate with equal hashes.
+fn group_by_hash(candidates: Vec<Candidate>, limit: Option<u64>, errors: &mut u64) -> Vec<Vec<(String, Candidate)>> {
+    let mut by_hash: HashMap<String, Vec<(String, Candidate)>> = HashMap::new();
+    for candidate in candidates {
+        match hash(&candidate.path, limit) {
+            Ok(hash) => by_hash.entry(hash.clone()).or_default().push((hash, candidate)),
+            Err(_) => *errors += 1,
+        }
+    }
+    by_hash.into_values().filter(|group| group.len() > 1).collect()
+}
+
+/// Returns the BLAKE3 hash of the first `limit` bytes of the file at `path`, or of all of it.
+fn hash(path: &Path, limit: Option<u64>) -> io::Result<String> {
+    let file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    match limit {
+        Some(limit) => hasher.update_reader(file.take(limit))?,
+        None => hasher.update_reader(file)?,
+    };
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Returns `true` if the filesystem containing `path` supports sharing data between files with
+/// reflinks, as btrfs and xfs do.
+pub fn supports_reflink(path: &Path) -> bool {
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+
+    // SAFETY: `c_path` is a valid NUL-terminated string and `stat` points to writable memory large
+    // enough for a `statfs` struct, which is only read after the call reported success.
+    let stat = unsafe {
+        if libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return false;
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)]
+    REFLINK_FILESYSTEMS.contains(&(stat.f_type as libc::c_long))
+}
@@ -0,0 +1,180 @@
+//! Space held by deleted files.
+//!
+//! Deleting a file only removes its name. While a process keeps it open, for example a service
+//! writing to a log that was deleted instead of rotated, its blocks stay allocated: `df` reports
+//! the disk as full while `du` cannot find the data. The space is freed once the file is
+//! truncated through the descriptor in `/proc/<pid>/fd`, or the process closes it, e.g. because
+//! its systemd unit is restarted.
+//!
+//! Shared memory is left out. memfd objects, System V segments and unlinked files in `/dev/shm`
+//! show up as deleted files too, but they live in memory rather than on disk, and truncating them
+//! would corrupt the processes sharing them.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashSet},
+    ffi::CString,
+    fs,
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use super::procfs::OpenFiles;
+
+/// The `statfs(2)` magic number of tmpfs, which also backs memfd objects and System V shared
+/// memory.
+const TMPFS_MAGIC: libc::c_long = 0x0102_1994;
+
+/// The link targets of shared memory: memfd objects, System V segments and POSIX shared memory.
+const SHARED_MEMORY_PREFIXES: [&str; 3] = ["/memfd:", "/SYSV", "/dev/shm/"];
+
+/// A deleted file that a process still holds open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeldFile {
+    /// The ID of the process holding the file.
+    pub pid: u32,
+    /// The descriptor number.
+    pub fd: u32,
+    /// The name of the process, from `/proc/<pid>/comm`.
+    pub process: String,
+    /// The systemd unit the process belongs to, if any.
+    pub unit: Option<String>,
+    /// The path the file had before it was deleted.
+    pub path: PathBuf,
+    /// The space allocated to the file, in bytes.
+    pub bytes: u64,
+    /// The device the file is stored on.
+    pub device: u64,
+    /// The inode number of the file, which identifies it on its device.
+    pub inode: u64,
+}
+
+impl HeldFile {
+    /// Returns the descriptor below `proc_dir`, e.g. `/proc/42/fd/3`, through which the file can
+    /// still be opened.
+    pub fn fd_path(&self, proc_dir: &Path) -> PathBuf {
+        proc_dir.join(self.pid.to_string()).join("fd").join(self.fd.to_string())
+    }
+}
+
+/// The deleted files held open by every process that could be inspected.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HeldSpace {
+    /// The held files, largest first.
+    pub files: Vec<HeldFile>,
+    /// The space held by all files, in bytes. Files open in several processes are counted once.
+    pub bytes: u64,
+    /// The space held per service or process, heaviest first.
+    pub holders: Vec<Holder>,
+    /// The number of processes whose descriptors could not be read.
+    pub inaccessible: usize,
+}
+
+/// The deleted files held open by a single systemd service, or by a process outside of one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Holder {
+    /// The name of the service, or the name and ID of the process, e.g. `app (42)`.
+    pub holder: String,
+    /// The number of held files.
+    pub files: u64,
+    /// The space held by the files, in bytes. Files open in several of its processes are counted
+    /// once.
+    pub bytes: u64,
+}
+
+/// Finds the deleted files held open by the processes below `proc_dir`.
+pub fn scan(proc_dir: &Path) -> HeldSpace {
+    let open_files = OpenFiles::scan(proc_dir);
+    let mut held = HeldSpace { inaccessible: open_files.inaccessible, ..HeldSpace::default() };
+    let mut seen = HashSet::new();
+
+    for file in open_files.files.into_iter().filter(|file| file.deleted) {
+        let process_dir = proc_dir.join(file.pid.to_string());
+        let fd_path = process_dir.join("fd").join(file.fd.to_string());
+        if is_shared_memory(&file.target, &fd_path) {
+            continue;
+        }
+        // The descriptor still leads to the file, so its metadata can be read through it.
+        let Ok(metadata) = fs::metadata(&fd_path) else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let bytes = metadata.blocks() * 512;
+        if seen.insert((metadata.dev(), metadata.ino())) {
+            held.bytes += bytes;
+        }
+        held.files.push(HeldFile {
+            pid: file.pid,
+            fd: file.fd,
+            process: fs::read_to_string(process_dir.join("comm")).unwrap_or_default().trim().to_string(),
+            unit: fs::read_to_string(process_dir.join("cgroup")).ok().as_deref().and_then(unit_of),
+            path: file.target,
+            bytes,
+            device: metadata.dev(),
+            inode: metadata.ino(),
+        });
+    }
+
+    held.files.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.pid.cmp(&b.pid)));
+    held.holders = by_holder(&held.files);
+    held
+}
+
+/// Sums the held files per systemd service, or per process for processes outside of one,
+/// heaviest first.
+pub fn by_holder(files: &[HeldFile]) -> Vec<Holder> {
+    let mut holders: BTreeMap<String, (Holder, HashSet<(u64, u64)>)> = BTreeMap::new();
+    for file in files {
+        let name =
+            file.unit.clone().unwrap_or_else(|| format!("{process} ({pid})", process = file.process, pid = file.pid));
+        let (holder, seen) = holders
+            .entry(name.clone())
+            .or_insert_with(|| (Holder { holder: name, files: 0, bytes: 0 }, HashSet::new()));
+        if seen.insert((file.device, file.inode)) {
+            holder.files += 1;
+            holder.bytes += file.bytes;
+        }
+    }
+    let mut holders: Vec<Holder> = holders.into_values().map(|(holder, _)| holder).collect();
+    holders.sort_by_key(|holder| Reverse(holder.bytes));
+    holders
+}
+
+/// Returns `true` if the descriptor at `fd_path`, whose link target is `target`, refers to shared
+/// memory or another file on a tmpfs rather than on disk.
+fn is_shared_memory(target: &Path, fd_path: &Path) -> bool {
+    let target = target.as_os_str().as_bytes();
+    if SHARED_MEMORY_PREFIXES.iter().any(|prefix| target.starts_with(prefix.as_bytes())) {
+        return true;
+    }
+    let Ok(c_path) = CString::new(fd_path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+
+    // SAFETY: `c_path` is a valid NUL-terminated string and `stat` points to writable memory large
+    // enough for a `statfs` struct, which is only read after the call reported success.
+    let stat = unsafe {
+        if libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return false;
+        }
+        stat.assume_init()
+    };
+    #[allow(clippy::unnecessary_cast)]
+    (stat.f_type as libc::c_long == TMPFS_MAGIC)
+}
+
+/// Returns the systemd service of a process from the contents of `/proc/<pid>/cgroup`, e.g.
+/// `nginx.service` for `0::/system.slice/nginx.service`.
+pub fn unit_of(cgroup: &str) -> Option<String> {
+    cgroup.lines().find_map(|line| {
+        let (_, path) = line.rsplit_once(':')?;
+        path.split('/').rev().find(|component| component.ends_with(".service")).map(String::from)
+    })
+}
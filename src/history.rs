@@ -0,0 +1,328 @@
+//! The history of runs and package removals.
+//!
+//! The history is kept in `$XDG_STATE_HOME/disk-space-optimizer` (usually `~/.local/state`) as
+//! JSON Lines files, so that records are only ever appended. `runs.jsonl` holds every invocation
+//! of the tool with the tasks it ran, the commands they executed and the space they reclaimed.
+//! `removals.jsonl` holds every package removal with the packages that disappeared and, on dnf,
+//! the ID of the transaction. The `undo` command uses it to bring the packages back: with
+//! `dnf history undo` where a transaction was recorded, and by installing the recorded packages
+//! otherwise.
+
+use std::{
+    collections::HashSet,
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{backend::Package, report::Report};
+
+/// The name of the run history file in the state directory.
+const RUNS: &str = "runs.jsonl";
+
+/// The name of the removal history file in the state directory.
+const REMOVALS: &str = "removals.jsonl";
+
+/// A single invocation of the tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Run {
+    /// The ID of the run.
+    pub run_id: String,
+    /// The time the run finished.
+    pub time: SystemTime,
+    /// Whether the tasks were selected in the menu rather than given on the command line.
+    pub menu: bool,
+    /// Whether the tasks only printed their plan.
+    pub dry_run: bool,
+    /// The tasks, in execution order.
+    pub tasks: Vec<TaskRun>,
+}
+
+/// A task run as part of a `Run`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRun {
+    /// The human readable name of the task.
+    pub task: String,
+    /// Every external command the task ran, in order.
+    pub commands: Vec<ExecutedCommand>,
+    /// The bytes freed on the watched filesystems, or `None` if space was not measured.
+    pub bytes_reclaimed: Option<i64>,
+    /// The errors the task failed with.
+    pub errors: Vec<String>,
+}
+
+/// An external command run by a task. Unlike in the report, its output is not kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutedCommand {
+    /// The program followed by its arguments.
+    pub argv: Vec<String>,
+    /// The exit code, or `None` if the process was terminated by a signal or failed to start.
+    pub exit_code: Option<i32>,
+}
+
+impl Run {
+    /// Creates the record of the run `run_id` that finished at `time` with `report`.
+    pub fn new(run_id: &str, time: SystemTime, menu: bool, report: &Report) -> Self {
+        let tasks = report
+            .tasks
+            .iter()
+            .map(|task| TaskRun {
+                task: task.task.clone(),
+                commands: task
+                    .commands
+                    .iter()
+                    .map(|command| ExecutedCommand { argv: command.argv.clone(), exit_code: command.exit_code })
+                    .collect(),
+                bytes_reclaimed: task.bytes_reclaimed,
+                errors: task.errors.clone(),
+            })
+            .collect();
+        Self { run_id: run_id.to_string(), time, menu, dry_run: report.dry_run, tasks }
+    }
+
+    /// Returns `true` if any task of the run failed.
+    pub fn failed(&self) -> bool {
+        self.tasks.iter().any(|task| !task.errors.is_empty())
+    }
+}
+
+/// Selects the runs shown by the `history` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Only runs that finished at or after this time.
+    pub since: Option<SystemTime>,
+    /// Only the tasks whose name contains this text, ignoring case. Runs without such a task are
+    /// left out.
+    pub task: Option<String>,
+    /// Only runs in which a task failed.
+    pub failed: bool,
+}
+
+impl Filter {
+    /// Returns the runs matching the filter, keeping only the matching tasks of each.
+    pub fn apply(&self, runs: Vec<Run>) -> Vec<Run> {
+        let task = self.task.as_ref().map(|task| task.to_lowercase());
+        runs.into_iter()
+            .filter(|run| self.since.is_none_or(|since| run.time >= since))
+            .filter(|run| !self.failed || run.failed())
+            .filter_map(|mut run| {
+                if let Some(task) = &task {
+                    run.tasks.retain(|task_run| task_run.task.to_lowercase().contains(task));
+                }
+                (!run.tasks.is_empty()).then_some(run)
+            })
+            .collect()
+    }
+}
+
+/// The length of the periods the summary of reclaimed space is split into.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Period {
+    /// Calendar days.
+    Day,
+    /// Weeks starting on Monday.
+    Week,
+    /// Calendar months.
+    #[default]
+    Month,
+}
+
+/// The space reclaimed by a task within a period.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TaskSummary {
+    /// The first day of the period, e.g. `2023-05-01`.
+    pub period: String,
+    /// The human readable name of the task.
+    pub task: String,
+    /// The number of times the task ran in the period, dry runs excluded.
+    pub runs: usize,
+    /// The bytes freed by all these runs.
+    pub bytes_reclaimed: i64,
+}
+
+/// Sums the space reclaimed by each task per `period`, oldest period first and the tasks that
+/// reclaimed the most first within each period. Dry runs are left out.
+pub fn summarize(runs: &[Run], period: Period) -> Vec<TaskSummary> {
+    let mut summaries: Vec<TaskSummary> = Vec::new();
+    for run in runs.iter().filter(|run| !run.dry_run) {
+        let start = period_start(run.time, period);
+        for task in &run.tasks {
+            let bytes = task.bytes_reclaimed.unwrap_or_default();
+            match summaries.iter_mut().find(|summary| summary.period == start && summary.task == task.task) {
+                Some(summary) => {
+                    summary.runs += 1;
+                    summary.bytes_reclaimed += bytes;
+                }
+                None => summaries.push(TaskSummary {
+                    period: start.clone(),
+                    task: task.task.clone(),
+                    runs: 1,
+                    bytes_reclaimed: bytes,
+                }),
+            }
+        }
+    }
+    summaries.sort_by(|a, b| a.period.cmp(&b.period).then_with(|| b.bytes_reclaimed.cmp(&a.bytes_reclaimed)));
+    summaries
+}
+
+/// Returns the first day of the `period` containing `time`, e.g. `2023-05-01`.
+fn period_start(time: SystemTime, period: Period) -> String {
+    let days = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs() / 86_400;
+    let (year, month, day) = match period {
+        Period::Day => civil_from_days(days),
+        // The epoch was a Thursday.
+        Period::Week => civil_from_days(days.saturating_sub((days + 3) % 7)),
+        Period::Month => {
+            let (year, month, _) = civil_from_days(days);
+            (year, month, 1)
+        }
+    };
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// A package removal performed by the tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Removal {
+    /// The ID of the run of the tool that removed the packages.
+    pub run_id: String,
+    /// The time the packages were removed.
+    pub time: SystemTime,
+    /// The package manager backend, e.g. `dnf`.
+    pub backend: String,
+    /// The task that removed the packages, e.g. "Uninstall unused applications".
+    pub task: String,
+    /// The packages that were removed, including dependencies removed with them.
+    pub packages: Vec<String>,
+    /// The ID of the package manager transaction, if the package manager keeps a history.
+    pub transaction: Option<String>,
+}
+
+/// Returns a new run ID, unique per invocation of the tool.
+pub fn new_run_id(now: SystemTime) -> String {
+    let secs = now.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    format!("{secs}-{pid}", pid = process::id())
+}
+
+/// Returns the directory holding the history, if the home directory is known.
+pub fn state_dir() -> Option<PathBuf> {
+    let state_home = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))?;
+    Some(state_home.join("disk-space-optimizer"))
+}
+
+/// Appends `run` to the run history in `state_dir`.
+///
+/// # Errors
+///
+/// Returns an error if the history file cannot be written.
+pub fn record_run(state_dir: &Path, run: &Run) -> Result<()> {
+    append(&state_dir.join(RUNS), run)
+}
+
+/// Reads the run history in `state_dir`, oldest first. Lines that cannot be parsed are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the history file exists but cannot be read.
+pub fn runs(state_dir: &Path) -> Result<Vec<Run>> {
+    read(&state_dir.join(RUNS))
+}
+
+/// Appends `removal` to the removal history in `state_dir`.
+///
+/// # Errors
+///
+/// Returns an error if the history file cannot be written.
+pub fn record(state_dir: &Path, removal: &Removal) -> Result<()> {
+    append(&state_dir.join(REMOVALS), removal)
+}
+
+/// Reads the removal history in `state_dir`, oldest first. Lines that cannot be parsed are skipped.
+///
+/// # Errors
+///
+/// Returns an error if the history file exists but cannot be read.
+pub fn removals(state_dir: &Path) -> Result<Vec<Removal>> {
+    read(&state_dir.join(REMOVALS))
+}
+
+/// Appends `record` to the JSON Lines file at `path`, creating it and its directory if needed.
+fn append<T: Serialize>(path: &Path, record: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Reads the records of the JSON Lines file at `path`. A missing file has no records.
+fn read<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    Ok(contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Returns the names of the packages in `before` that are missing from `after`.
+pub fn removed_packages(before: &[Package], after: &[Package]) -> Vec<String> {
+    let after: HashSet<(&str, &str)> =
+        after.iter().map(|package| (package.name.as_str(), package.arch.as_str())).collect();
+    let mut removed: Vec<String> = before
+        .iter()
+        .filter(|package| !after.contains(&(package.name.as_str(), package.arch.as_str())))
+        .map(|package| package.name.clone())
+        .collect();
+    removed.sort();
+    removed.dedup();
+    removed
+}
+
+/// Formats `time` as a UTC date and time, e.g. `2023-05-01 10:00`.
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    let (hour, minute) = (secs % 86_400 / 3_600, secs % 3_600 / 60);
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}")
+}
+
+/// Converts a year, month and day to days since the epoch, the inverse of `civil_from_days`, see
+/// https://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Converts days since the epoch to a year, month and day, see
+/// https://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn civil_from_days(days: u64) -> (i64, i64, i64) {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,139 @@
+//! Systemd journal usage.
+//!
+//! Vacuuming the journal frees space once, but a unit that logs in a tight loop fills it again. The
+//! `journal-usage` command reads `journalctl -o json` and sums the size of the log messages per
+//! systemd unit and per boot, so that noisy sources can be fixed where they log.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{execute_cmd, runner::CommandRunner};
+
+/// The log volume of a single unit or boot.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Source {
+    /// The unit name, e.g. `NetworkManager.service`, or the boot ID.
+    pub name: String,
+    /// The number of journal entries.
+    pub entries: u64,
+    /// The total length of the messages, in bytes.
+    pub bytes: u64,
+}
+
+/// The log volume of the journal, broken down by unit and by boot.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct JournalUsage {
+    /// The number of journal entries read.
+    pub entries: u64,
+    /// The total length of all messages, in bytes.
+    pub bytes: u64,
+    /// The units that logged the most, by message bytes, heaviest first.
+    pub units: Vec<Source>,
+    /// The boots that logged the most, by message bytes, heaviest first.
+    pub boots: Vec<Source>,
+}
+
+/// Reads the journal since `since` (any time specification `journalctl --since` accepts, or the
+/// whole journal when `None`) and returns the `top` heaviest units and boots.
+///
+/// # Errors
+///
+/// Returns an error if `journalctl` fails.
+pub fn usage(runner: &dyn CommandRunner, since: Option<&str>, top: usize) -> Result<JournalUsage> {
+    // `_BOOT_ID` is always part of the output, even when not listed in `--output-fields`.
+    let mut args = vec!["--output=json", "--output-fields=MESSAGE,_SYSTEMD_UNIT,SYSLOG_IDENTIFIER,_TRANSPORT"];
+    let since = since.map(|since| format!("--since={since}"));
+    args.extend(since.as_deref());
+
+    let output = runner.run("journalctl", &args, None).context("Failed to read the journal")?;
+    if !output.success() {
+        return Err(anyhow!("journalctl failed: {}", output.stderr.trim()));
+    }
+    Ok(parse_usage(&output.stdout, top))
+}
+
+/// Sums the entries of `journalctl -o json` output per unit and per boot. Lines that are not valid
+/// JSON are skipped.
+pub fn parse_usage(output: &str, top: usize) -> JournalUsage {
+    let mut usage = JournalUsage::default();
+    let mut units: HashMap<String, Source> = HashMap::new();
+    let mut boots: HashMap<String, Source> = HashMap::new();
+
+    for line in output.lines() {
+        let Ok(Value::Object(entry)) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let field = |name: &str| entry.get(name).and_then(Value::as_str);
+
+        // Binary messages are exported as arrays of bytes.
+        let bytes = match entry.get("MESSAGE") {
+            Some(Value::String(message)) => message.len() as u64,
+            Some(Value::Array(message)) => message.len() as u64,
+            _ => 0,
+        };
+        let unit = field("_SYSTEMD_UNIT")
+            .or_else(|| field("SYSLOG_IDENTIFIER"))
+            .or_else(|| field("_TRANSPORT").filter(|transport| *transport == "kernel"))
+            .unwrap_or("unknown");
+        let boot = field("_BOOT_ID").unwrap_or("unknown");
+
+        usage.entries += 1;
+        usage.bytes += bytes;
+        for (sources, name) in [(&mut units, unit), (&mut boots, boot)] {
+            let source = sources
+                .entry(name.to_string())
+                .or_insert_with(|| Source { name: name.to_string(), ..Source::default() });
+            source.entries += 1;
+            source.bytes += bytes;
+        }
+    }
+
+    usage.units = heaviest(units, top);
+    usage.boots = heaviest(boots, top);
+    usage
+}
+
+/// Returns the `top` sources with the most message bytes, heaviest first.
+fn heaviest(sources: HashMap<String, Source>, top: usize) -> Vec<Source> {
+    let mut sources: Vec<Source> = sources.into_values().collect();
+    sources.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    sources.truncate(top);
+    sources
+}
+
+/// Returns the size reported by `journalctl --disk-usage`, e.g. `3.9G`.
+///
+/// # Errors
+///
+/// Returns an error if `journalctl` fails.
+pub fn disk_usage(runner: &dyn CommandRunner) -> Result<String> {
+    let output = execute_cmd(runner, "journalctl", &["--disk-usage"])?;
+    Ok(parse_disk_usage(&output).unwrap_or(output))
+}
+
+/// Extracts the size from a line such as `Archived and active journals take up 3.9G in the file
+/// system.`
+pub fn parse_disk_usage(output: &str) -> Option<String> {
+    let (_, rest) = output.split_once("take up ")?;
+    rest.split_whitespace().next().map(String::from)
+}
+
+/// Converts a size printed by `journalctl`, such as `3.9G` or `512.0K`, into bytes. The units are
+/// powers of 1024.
+pub fn parse_bytes(size: &str) -> Option<u64> {
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let exponent = match unit {
+        "" | "B" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * 1024f64.powi(exponent)) as u64)
+}
@@ -0,0 +1,2191 @@
+//! # Disk Space Optimizer
+//!
+//! A library for optimizing disk space on Linux systems, and the command-line interface (CLI)
+//! built on top of it.
+//!
+//! Every cleanup is a `cli::Commands` variant that runs within a `session::Session`. Commands
+//! first build a `plan::Plan` of external commands, which is executed or, in dry-run mode,
+//! printed. The scanners behind them, such as `analyze::scan`, `duplicates::find` or
+//! `targets::find`, return structured results, so other tools can use them directly.
+//! `execute_and_measure` records the outcome of each command in a `report::Report`.
+//!
+//! The library prints nothing by itself: the messages of the commands go to the reporter installed
+//! with `output::set_reporter`, and are discarded without one.
+//!
+//! # Usage
+//!
+//! The CLI is executed using the following command:
+//!
+//! ```bash
+//! cargo run --bin disk-space-optimizer
+//! ```
+#![deny(missing_docs)]
+
+#[cfg(test)]
+mod tests;
+
+pub mod analyze;
+pub mod auto;
+pub mod backend;
+pub mod config;
+pub mod devcache;
+pub mod duplicates;
+pub mod held;
+pub mod history;
+pub mod journal;
+pub mod logs;
+pub mod output;
+pub mod plan;
+pub mod procfs;
+pub mod quarantine;
+pub mod reclaim;
+pub mod report;
+pub mod runner;
+pub mod safeguard;
+pub mod session;
+pub mod space;
+pub mod targets;
+pub mod task;
+pub mod version;
+
+use std::{
+    io::{self, prelude::*},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+//////////////////////////////////////////////////////////////////////////////////////////////////
+// REGION_START: helper functions
+//////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// The `get_commands` function returns a `DiskSpaceOptimizerItems` object that contains a set of
+/// options for disk space optimization.
+///
+/// Each option is represented as a `SelectableItem`, which is a struct containing a numeric ID and
+/// a string label.
+///
+/// There is one option for each task of `task::TASKS`, keyed by its position starting at 1, with
+/// the description and risk of the task as help, followed by "Exit" with the key 0. Tasks missing
+/// from the `[tasks] enabled` list of the configuration are left out.
+///
+/// # Examples
+///
+/// ```
+/// use disk_space_optimizer::{config::Config, get_commands};
+///
+/// let commands = get_commands(&Config::default());
+///
+/// for item in commands {
+///     println!("{}. {}", item.key, item.text);
+/// }
+/// ```
+pub fn get_commands(config: &config::Config) -> multidialogue::DiskSpaceOptimizerItems<i32> {
+    let options = task::TASKS
+        .iter()
+        .zip(1..)
+        .filter(|(task, _)| config.is_enabled(task.id()))
+        .map(|(task, key)| {
+            let help = format!("{description} Risk: {risk}.", description = task.description(), risk = task.risk());
+            multidialogue::SelectableItem::new(key, task.title()).with_help(&help)
+        })
+        .collect();
+
+    multidialogue::DiskSpaceOptimizerItems::new()
+        .with_options(options)
+        .with_option(multidialogue::SelectableItem::new(0, "Exit"))
+}
+
+/// Annotates the menu `commands` with the space each task would reclaim with its defaults, as
+/// estimated by `task::estimates`. The estimates run concurrently, so the slowest one decides how
+/// long this takes. Items that are no task, such as "Exit", are left as they are.
+pub fn estimate_commands(
+    session: &session::Session,
+    commands: multidialogue::DiskSpaceOptimizerItems<i32>,
+) -> multidialogue::DiskSpaceOptimizerItems<i32> {
+    let mut items: Vec<_> = commands.into_iter().collect();
+    let (indices, tasks): (Vec<usize>, Vec<&dyn task::Task>) =
+        items.iter().enumerate().filter_map(|(index, item)| Some((index, task::by_key(item.key as usize)?))).unzip();
+    for (index, estimate) in indices.into_iter().zip(task::estimates(session, &tasks)) {
+        items[index].estimate = estimate;
+    }
+    multidialogue::DiskSpaceOptimizerItems::new().with_options(items)
+}
+
+/// Executes a command, recording its outcome and the space it reclaimed in `report`.
+///
+/// Free space is sampled on the filesystems of `space::WATCHED_PATHS` and of the paths the command
+/// names with `Commands::locations`. In dry-run mode, and for commands that only inspect the
+/// system, nothing is removed, so the command is executed without sampling free space.
+///
+/// # Errors
+///
+/// Returns the error of the executed command.
+pub fn execute_and_measure(
+    command: &cli::Commands,
+    session: &session::Session,
+    report: &mut report::Report,
+) -> Result<()> {
+    let locations = (!(session.dry_run || command.is_read_only())).then(|| command.locations());
+    report.run(command.title(), locations.as_deref(), || command.execute(session), || session.runner.take_records())
+}
+
+/// Appends the tasks run by `command`, or by the commands selected in the menu when `None`, to
+/// the run history.
+///
+/// Nothing is recorded when the history is unavailable, when no task ran, for `history` itself, or
+/// for an `auto` run that had nothing to do, so that hourly runs do not flood the history.
+///
+/// # Errors
+///
+/// Returns an error if the history cannot be written.
+pub fn record_run(command: Option<&cli::Commands>, session: &session::Session, report: &report::Report) -> Result<()> {
+    let Some(state_dir) = &session.state_dir else {
+        return Ok(());
+    };
+    if report.tasks.is_empty() || matches!(command, Some(cli::Commands::History { .. })) {
+        return Ok(());
+    }
+    if matches!(command, Some(cli::Commands::Auto { .. }))
+        && auto::exit_code(report) == Some(auto::Status::NothingToDo.exit_code())
+    {
+        return Ok(());
+    }
+    let run = history::Run::new(&session.run_id, SystemTime::now(), command.is_none(), report);
+    history::record_run(state_dir, &run)
+}
+
+/// Runs `cmd` with `args` through `runner` and returns its standard output without surrounding
+/// whitespace. The command line and its output are reported through `say!`.
+///
+/// # Errors
+///
+/// Returns an error if the command cannot be started, or if it exits with a non-zero status or is
+/// killed by a signal. The error then includes the exit code and the standard error output.
+///
+/// # Examples
+///
+/// ```no_run
+/// use anyhow::Result;
+/// use disk_space_optimizer::{execute_cmd, runner::SystemRunner};
+///
+/// fn main() -> Result<()> {
+///     let output = execute_cmd(&SystemRunner, "git", &["status"])?;
+///     println!("{}", output);
+///     Ok(())
+/// }
+/// ```
+pub fn execute_cmd(runner: &dyn runner::CommandRunner, cmd: &str, args: &[&str]) -> Result<String> {
+    let cmd_str = format!("{cmd} {args}", cmd = cmd, args = args.join(" "));
+    say!("Executing: {cmd_str}", cmd_str = cmd_str,);
+
+    let output = runner
+        .run(cmd, args, None)
+        .with_context(|| anyhow!(format!("Failed to execute command: {cmd_str}", cmd_str = cmd_str)))?;
+
+    if output.success() {
+        say!("Command output:\n{}", output.stdout);
+        Ok(output.stdout.trim().to_string())
+    } else {
+        let status = output.status.map_or_else(|| String::from("signal"), |code| code.to_string());
+        Err(anyhow!("Command failed with exit code {}: {}", status, output.stderr.trim()))
+    }
+}
+
+/// Reads a line of text from standard input and returns it as a `String`.
+///
+/// # Errors
+///
+/// Returns an error if flushing standard output fails or if reading from standard input fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use anyhow::Result;
+/// use disk_space_optimizer::read_line;
+///
+/// fn main() -> Result<()> {
+///     let input = read_line()?;
+///     println!("You entered: {}", input);
+///     Ok(())
+/// }
+/// ```
+pub fn read_line() -> Result<String> {
+    let mut buffer = String::new();
+    io::stdout().flush().context("Failed to flush stdout")?;
+    io::stdin().read_line(&mut buffer).with_context(|| "Failed to read input")?;
+
+    Ok(buffer)
+}
+
+/// Reads a file and returns it as a `Vec<String>`.
+///
+/// # Arguments
+///
+/// * `filename` - The path to the file to read.
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be read.
+#[allow(dead_code)]
+fn read_file(filename: &str) -> io::Result<Vec<String>> {
+    let file = std::fs::File::open(filename)?;
+    let lines = io::BufReader::new(file).lines().collect::<io::Result<Vec<String>>>()?;
+
+    Ok(lines)
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////
+// REGION_END: helper functions
+//////////////////////////////////////////////////////////////////////////////////////////////////
+
+//////////////////////////////////////////////////////////////////////////////////////////////////
+// REGION_START: mod multidialogue
+//////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub mod multidialogue {
+    //! The interactive menu that lets the user pick several tasks at once.
+
+    use std::{cmp, fmt};
+
+    use anyhow::{Error, Result};
+    use dialoguer::{theme::ColorfulTheme, MultiSelect};
+
+    use crate::{output::say, space::format_bytes};
+
+    /// Runs the dialoguer prompt for selecting one or more options from the list of selectable
+    /// items.
+    ///
+    /// # Arguments
+    ///
+    /// * `items`: A reference to `DiskSpaceOptimizerItems<T>` containing the list of selectable items.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a vector of references to `SelectableItem<T>` structs that
+    /// were selected by the user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an issue with the prompt interaction.
+    pub fn run_dialoguer<T>(items: &DiskSpaceOptimizerItems<T>) -> Result<Vec<&SelectableItem<T>>>
+    where
+        T: fmt::Display + fmt::Debug + cmp::PartialEq<i32> + cmp::Eq + Copy,
+    {
+        // Set defaults for the prompt.
+        let options = &mut items.options.as_ref().unwrap();
+        let mut defaults: Vec<bool> = vec![false; options.len()];
+        if let Some(last) = defaults.last_mut() {
+            let opt = options.last().unwrap();
+            debug_assert_eq!(opt.key, 0);
+            debug_assert_eq!(opt.text, String::from("Exit"));
+            *last = true; // The last command exit will always be selected by default.
+        }
+
+        debug_assert_eq!(items.options.as_ref().unwrap().len(), defaults.len());
+
+        // The prompt has no room for help, so it is printed above it.
+        for item in options.iter() {
+            if let Some(help) = &item.help {
+                say!("  {key}: {text} - {help}", key = item.key, text = item.text);
+            }
+        }
+
+        // Run the dialoguer prompt.
+        let selections: Vec<usize> = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Please select an option: (space to select, enter to confirm)")
+            .items(&format_prompt_item(items).unwrap())
+            .defaults(&defaults[..])
+            .interact()?;
+
+        // Return the selected items.
+        let selections: Vec<&SelectableItem<T>> =
+            selections.into_iter().map(|i| &items.options.as_ref().unwrap().as_slice()[i]).collect();
+
+        Ok(selections)
+    }
+
+    /// Formats the prompt items for the dialoguer prompt.
+    ///
+    /// # Arguments
+    ///
+    /// * `items`: A reference to `DiskSpaceOptimizerItems<T>` containing the list of selectable items.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a vector of formatted strings representing the selectable
+    /// items.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Error` if there is an issue with formatting the prompt items.
+    pub fn format_prompt_item<T>(items: &DiskSpaceOptimizerItems<T>) -> Result<Vec<String>, Error>
+    where
+        T: fmt::Display,
+    {
+        let res = items
+            .options
+            .as_ref()
+            .unwrap_or(&vec![])
+            .iter()
+            .map(|item: &SelectableItem<T>| match item.estimate {
+                Some(bytes) => format!(
+                    "{key}: {text} (~{size})",
+                    key = item.key,
+                    text = &item.text[..],
+                    size = format_bytes(bytes)
+                ),
+                None => format!("{key}: {text}", key = item.key, text = &item.text[..]),
+            })
+            .collect();
+
+        Ok(res)
+    }
+
+    /// Represents a selectable item in the disk space optimizer. It contains a key of type `T` and
+    /// a text description. It can be cloned if the key type implements `Clone`, and can be
+    /// created using `SelectableItem::new`.
+    #[derive(Debug)]
+    pub struct SelectableItem<T> {
+        /// The key identifying the item, e.g. its number in the menu.
+        pub key: T,
+        /// The text shown for the item.
+        pub text: String,
+        /// A longer explanation of the item, printed above the prompt.
+        pub help: Option<String>,
+        /// The estimated space selecting the item would reclaim, in bytes, shown next to the text.
+        pub estimate: Option<u64>,
+    }
+
+    impl<T: Clone> Clone for SelectableItem<T> {
+        fn clone(&self) -> Self {
+            SelectableItem {
+                key: self.key.clone(),
+                text: self.text.clone(),
+                help: self.help.clone(),
+                estimate: self.estimate,
+            }
+        }
+    }
+
+    impl<T: ToString> SelectableItem<T> {
+        /// Creates a new `SelectableItem` with the given key and text.
+        pub fn new(key: T, text: &str) -> Self {
+            Self { key, text: text.to_string(), help: None, estimate: None }
+        }
+
+        /// Sets the help printed for the item.
+        pub fn with_help(mut self, help: &str) -> Self {
+            self.help = Some(help.to_string());
+            self
+        }
+    }
+
+    /// Represents a collection of selectable items in the disk space optimizer.
+    ///
+    /// It can be iterated over using a for loop, and can be created using
+    /// `DiskSpaceOptimizerItems::new`, and then adding options using
+    /// `DiskSpaceOptimizerItems::with_option` or `DiskSpaceOptimizerItems::with_options`.
+    #[derive(Clone)]
+    pub struct DiskSpaceOptimizerItems<T> {
+        /// The items, in the order they are shown.
+        pub options: Option<Vec<SelectableItem<T>>>,
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for DiskSpaceOptimizerItems<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "DiskSpaceOptimizerItems {{ options: ")?;
+            if let Some(options) = &self.options {
+                write!(f, "{:?}", options)?;
+            } else {
+                write!(f, "None")?;
+            }
+            write!(f, " }}")
+        }
+    }
+
+    impl<T> std::iter::IntoIterator for DiskSpaceOptimizerItems<T> {
+        type Item = SelectableItem<T>;
+        type IntoIter = std::vec::IntoIter<Self::Item>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.options.unwrap_or_default().into_iter()
+        }
+    }
+
+    impl<T> Default for DiskSpaceOptimizerItems<T> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<T> DiskSpaceOptimizerItems<T> {
+        /// Creates a new `DiskSpaceOptimizerItems` instance with no options.
+        pub fn new() -> Self {
+            Self { options: None }
+        }
+
+        /// Adds the given option to the list of options.
+        pub fn with_option(mut self, option: SelectableItem<T>) -> Self {
+            let mut options = self.options.unwrap_or_default();
+            options.push(option);
+            self.options = Some(options);
+            self
+        }
+
+        /// Adds the given list of options to the list of options.
+        pub fn with_options(mut self, options: Vec<SelectableItem<T>>) -> Self {
+            self.options = Some(options);
+            self
+        }
+    }
+}
+//////////////////////////////////////////////////////////////////////////////////////////////////
+// REGION_END: mod multidialogue
+//////////////////////////////////////////////////////////////////////////////////////////////////
+
+//////////////////////////////////////////////////////////////////////////////////////////////////
+// REGION_START: mod cli
+//////////////////////////////////////////////////////////////////////////////////////////////////
+pub mod cli {
+    //! The command-line interface (CLI) of the disk space optimizer.
+    //!
+    //! `Cli` is the top-level command, parsed with `clap`. It holds the global flags, such as
+    //! `--dry-run` and `--format`, and an optional `Commands` subcommand; the menu is shown when
+    //! none is given.
+    //!
+    //! Every subcommand is a variant of `Commands`. The cleanups that can run with their defaults
+    //! are also tasks in `task::TASKS`, which is the single list the menu is built from:
+    //! `Commands::from_selection` maps a menu key to the command of the task at that position, task
+    //! commands take their title from the registry, and `Cli::command_with_tasks` adds the
+    //! description and risk of each task to the help of its subcommand. The other commands, such
+    //! as `analyze`, `history` or `reclaim`, are only available on the command line.
+    //!
+    //! `Commands::execute` runs a command within a `Session`. Most commands first build a `Plan` of
+    //! external commands, which is printed in dry-run mode and otherwise executed, after a
+    //! confirmation when it deletes files or removes packages.
+
+    use std::{
+        os::unix::fs::MetadataExt,
+        path::{Path, PathBuf},
+        time::{Duration, SystemTime},
+    };
+
+    use anyhow::{anyhow, Error, Result};
+    use clap::{builder::PossibleValuesParser, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+    use super::{
+        analyze::{self, Analysis, ScanOptions},
+        auto,
+        backend::{Backend, Package},
+        devcache, duplicates, held,
+        history::{self, Period, Removal, Run},
+        journal, logs, multidialogue,
+        output::{say, Format},
+        plan::{Plan, Step},
+        procfs::{OpenFiles, PROC_DIR},
+        quarantine, reclaim,
+        safeguard::{ProtectedKernels, BOOT_DIR},
+        session::Session,
+        space::{self, format_bytes, format_signed_bytes},
+        targets, task, version,
+    };
+    // use std::io::BufRead;
+
+    /// The number of newest kernels `remove-old-kernels` keeps unless `--keep` says otherwise.
+    pub const DEFAULT_KEPT_KERNELS: usize = 2;
+
+    /// The number of days of journal entries `clean-up-log-files` keeps unless a limit is given.
+    pub const DEFAULT_VACUUM_DAYS: u32 = 7;
+
+    impl Cli {
+        /// Parses the command-line arguments like `Cli::parse`, with the help of each task
+        /// extended by its description and risk from `task::TASKS`.
+        pub fn parse_with_tasks() -> Self {
+            let matches = Self::command_with_tasks().get_matches();
+            Self::from_arg_matches(&matches).unwrap_or_else(|err| err.exit())
+        }
+
+        /// Returns the clap command of the CLI, with the long help of each task subcommand
+        /// extended by its description and risk from `task::TASKS`.
+        pub fn command_with_tasks() -> clap::Command {
+            task::TASKS.iter().fold(Self::command(), |command, task| {
+                command.mut_subcommand(task.id(), |subcommand| {
+                    let about = subcommand.get_about().map(ToString::to_string).unwrap_or_default();
+                    let help = format!(
+                        "{about}\n\n{description} Risk: {risk}.",
+                        description = task.description(),
+                        risk = task.risk()
+                    );
+                    subcommand.long_about(help)
+                })
+            })
+        }
+    }
+
+    /// A command-line interface tool for optimizing disk space.
+    #[derive(Parser, Debug)]
+    #[command(author,version,about,long_about=None)]
+    #[clap(name = "Disk Space Optimizer", about = "A CLI tool for optimizing disk space")]
+    pub struct Cli {
+        /// The package manager backend to use. Detected from `/etc/os-release` when omitted.
+        #[arg(long, global = true, value_enum)]
+        pub backend: Option<Backend>,
+
+        /// Print the commands that would run, and what they would remove, without executing them.
+        #[arg(long, global = true)]
+        pub dry_run: bool,
+
+        /// The output format. `json` prints a single result document on stdout and moves all other
+        /// output to stderr.
+        #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+        pub format: Format,
+
+        /// Answer yes to every confirmation.
+        #[arg(short, long, visible_alias = "assume-yes", global = true)]
+        pub yes: bool,
+
+        /// Never prompt. Commands fail when they would need input that was not given as arguments.
+        /// Implied when stdin is not a terminal.
+        #[arg(long, global = true)]
+        pub non_interactive: bool,
+
+        /// Move files into the quarantine instead of deleting them, so that they can be restored
+        /// with `restore` until `purge-quarantine` deletes them.
+        #[arg(long, global = true)]
+        pub quarantine: bool,
+
+        /// The command to run. The menu is shown when omitted.
+        #[command(subcommand)]
+        pub command: Option<Commands>,
+    }
+
+    /// The available commands that the tool supports.
+    #[derive(Subcommand, Debug, Clone)]
+    pub enum Commands {
+        /// Removes packages with the specified names.
+        // #[structopt(name = "remove-package", about = "Remove a package")]
+        RemovePackage {
+            /// The names of the packages to remove. Prompts for a selection when omitted.
+            // #[structopt(name = "PACKAGE_NAME", help = "Name of the package to remove")]
+            packages: Vec<String>,
+        },
+
+        /// Cleans the package cache.
+        CleanPackageCache,
+
+        /// Uninstalls unused apps.
+        UninstallUnusedApps,
+
+        /// Removes old kernels, keeping the newest ones.
+        RemoveOldKernels {
+            /// Keep the newest N kernels and remove the rest. When omitted, prompts for a selection
+            /// with all but the newest 2 kernels selected, or keeps 2 without a terminal.
+            #[arg(long)]
+            keep: Option<usize>,
+        },
+
+        /// Cleans up log files.
+        CleanUpLogFiles {
+            /// Remove journal entries older than this many days. Prompts for it when no limit is
+            /// given.
+            #[arg(long)]
+            vacuum_time: Option<u32>,
+
+            /// Shrink the archived journal files to this size, e.g. `500M` or `1G`.
+            #[arg(long)]
+            vacuum_size: Option<String>,
+
+            /// Keep at most this many archived journal files.
+            #[arg(long)]
+            vacuum_files: Option<u32>,
+
+            /// Archive the active journal files first, so that their entries can be vacuumed too.
+            #[arg(long)]
+            rotate: bool,
+
+            /// Delete rotated logs below `/var/log` older than this many days. Defaults to 30.
+            #[arg(long)]
+            log_max_age: Option<u64>,
+
+            /// Delete the oldest rotated logs until the remaining ones fit into this size, e.g. `1G`.
+            #[arg(long)]
+            log_max_size: Option<String>,
+
+            /// Do not compress the remaining rotated logs.
+            #[arg(long)]
+            no_compress: bool,
+
+            /// Only vacuum the journal, leaving the rotated logs below `/var/log` alone.
+            #[arg(long)]
+            journal_only: bool,
+        },
+
+        /// Finds deleted files that running processes still hold open, and frees their space.
+        HeldSpace {
+            /// Free the space of the selected files. Only lists them when omitted without a terminal.
+            #[arg(long, value_enum)]
+            action: Option<HeldAction>,
+
+            /// Act on every held file instead of prompting for a selection.
+            #[arg(long)]
+            all: bool,
+        },
+
+        /// Removes the build caches of developer tools such as Cargo, npm, pip and Go.
+        CleanDevCaches {
+            /// The tools whose caches to remove. Prompts for a selection when omitted, or only lists
+            /// the caches without a terminal.
+            #[arg(long = "tool", value_parser = PossibleValuesParser::new(devcache::TOOLS.map(|tool| tool.name)))]
+            tools: Vec<String>,
+
+            /// Remove the caches of every tool.
+            #[arg(long)]
+            all: bool,
+
+            /// Directories searched for stale `target/` and `node_modules` directories. Defaults to
+            /// the current directory.
+            #[arg(long = "root")]
+            roots: Vec<PathBuf>,
+
+            /// Only offer project directories that were not modified for this many days.
+            #[arg(long, default_value_t = devcache::DEFAULT_STALE_DAYS)]
+            stale_days: u64,
+        },
+
+        /// Removes old temporary files with `systemd-tmpfiles --clean`, honouring the ages set in
+        /// `tmpfiles.d`.
+        CleanTempFiles,
+
+        /// Finds the target directories of Cargo projects and removes the ones not built recently.
+        CargoTargets {
+            /// The directories to search. Defaults to the current directory.
+            roots: Vec<PathBuf>,
+
+            /// Remove target directories not built for this many days.
+            #[arg(long, default_value_t = devcache::DEFAULT_STALE_DAYS)]
+            older_than: u64,
+
+            /// Keep the target directories, and only remove their incremental compilation caches and
+            /// the profiles not built for `--older-than` days.
+            #[arg(long)]
+            prune: bool,
+        },
+
+        /// Finds files with identical contents and removes or links the extra copies.
+        Duplicates {
+            /// The files and directories to compare.
+            #[arg(required = true)]
+            paths: Vec<PathBuf>,
+
+            /// Deal with every set of duplicates this way, keeping the first copy in path order.
+            /// Prompts for each set when omitted, or only lists them without a terminal.
+            #[arg(long, value_enum)]
+            action: Option<DuplicateAction>,
+
+            /// Ignore files smaller than this, e.g. `100K` or `1M`.
+            #[arg(long)]
+            min_size: Option<String>,
+        },
+
+        /// Moves files into the quarantine, from where they can be restored.
+        Quarantine {
+            /// The files and directories to quarantine.
+            #[arg(required = true)]
+            paths: Vec<PathBuf>,
+        },
+
+        /// Moves quarantined items back to where they were. Lists the quarantine without arguments.
+        Restore {
+            /// The IDs of the items to restore, as listed by `restore`.
+            ids: Vec<String>,
+
+            /// Restore every quarantined item.
+            #[arg(long)]
+            all: bool,
+        },
+
+        /// Deletes quarantined items for good, freeing their space.
+        PurgeQuarantine {
+            /// Only delete items quarantined more than this many days ago.
+            #[arg(long, default_value_t = 0)]
+            older_than: u64,
+        },
+
+        /// Lists previous runs with the commands they executed and the space they reclaimed, newest
+        /// first, and sums up the space reclaimed per task.
+        History {
+            /// Only show runs of the last N days.
+            #[arg(long)]
+            since: Option<u64>,
+
+            /// Only show tasks whose name contains this text, e.g. `kernel`.
+            #[arg(long)]
+            task: Option<String>,
+
+            /// Only show runs in which a task failed.
+            #[arg(long)]
+            failed: bool,
+
+            /// Sum up the reclaimed space per day, week or month.
+            #[arg(long, value_enum, default_value_t = Period::Month)]
+            per: Period,
+        },
+
+        /// Reinstalls the packages removed by a previous run, with `dnf history undo` on dnf.
+        Undo {
+            /// The ID of the run, as listed by `history`.
+            run_id: String,
+        },
+
+        /// Frees a target amount of space on a filesystem with the least risky tasks, stopping once
+        /// the target is reached.
+        Reclaim {
+            /// The space to free, e.g. `10GiB` or `500M`.
+            #[arg(long)]
+            target: String,
+
+            /// A path on the filesystem to free the space on.
+            #[arg(long, default_value = "/")]
+            mount: PathBuf,
+        },
+
+        /// Runs the low-risk tasks configured under `[auto] tasks` without asking, but only when the
+        /// filesystem is fuller than a threshold. Meant for cron and systemd timers.
+        ///
+        /// Exits with 0 when the usage is not above the threshold or after a dry run, with 10 when
+        /// cleaning up brought it down, and with 11 when it is still above the threshold.
+        Auto {
+            /// The usage above which to clean up, e.g. `85%`.
+            #[arg(long, default_value = "85%")]
+            when_used_above: String,
+
+            /// A path on the filesystem to check.
+            #[arg(long, default_value = "/")]
+            mount: PathBuf,
+        },
+
+        /// Shows which systemd units and boots write the most to the journal.
+        JournalUsage {
+            /// Only read entries since this time, e.g. `yesterday` or `2023-05-01`.
+            #[arg(long)]
+            since: Option<String>,
+
+            /// The number of units and boots to show.
+            #[arg(short = 'n', long, default_value_t = 10)]
+            top: usize,
+        },
+
+        /// Analyzes disk usage below a path and shows the heaviest directories and files.
+        Analyze {
+            /// The directory to analyze.
+            #[arg(default_value = ".")]
+            path: PathBuf,
+
+            /// Do not descend into directories on other filesystems.
+            #[arg(short = 'x', long)]
+            one_file_system: bool,
+
+            /// The number of directories and files to show.
+            #[arg(short = 'n', long, default_value_t = 10)]
+            top: usize,
+        },
+    }
+
+    impl Commands {
+        /// Converts a user selection to a `Commands` enum variant.
+        ///
+        /// # Arguments
+        ///
+        /// * `selection` - A `usize` value representing the user's menu selection.
+        ///
+        /// # Returns
+        ///
+        /// An `Option<Commands>` representing the variant of the `Commands` enum associated with
+        /// the user's menu selection. Returns `None` if the selection is not a valid menu option.
+        ///
+        /// # Example
+        ///
+        /// ```
+        /// use disk_space_optimizer::cli::Commands;
+        ///
+        /// let selection = 1;
+        /// let command = Commands::from_selection(selection);
+        ///
+        /// match command {
+        ///     Some(Commands::RemovePackage { packages }) => {
+        ///         println!("Removing packages: {:?}", packages);
+        ///     }
+        ///     Some(Commands::CleanPackageCache) => {
+        ///         println!("Cleaning package cache...");
+        ///     }
+        ///     Some(Commands::UninstallUnusedApps) => {
+        ///         println!("Uninstalling unused apps...");
+        ///     }
+        ///     Some(Commands::RemoveOldKernels { .. }) => {
+        ///         println!("Removing old kernels...");
+        ///     }
+        ///     Some(Commands::CleanUpLogFiles { .. }) => {
+        ///         println!("Cleaning up log files...");
+        ///     }
+        ///     Some(command) => {
+        ///         println!("{}...", command.title());
+        ///     }
+        ///     None => {
+        ///         println!("Invalid selection.");
+        ///     }
+        /// }
+        /// ```
+        pub fn from_selection(selection: usize) -> Option<Self> {
+            task::by_key(selection).map(|task| task.command())
+        }
+
+        /// Returns the human readable title of the command. Task commands are titled by their task
+        /// in `task::TASKS`, as shown in the menu.
+        pub fn title(&self) -> &'static str {
+            match self {
+                Commands::CargoTargets { .. } => "Remove stale Cargo target directories",
+                Commands::Duplicates { .. } => "Remove duplicate files",
+                Commands::Quarantine { .. } => "Quarantine files",
+                Commands::Restore { .. } => "Restore quarantined files",
+                Commands::PurgeQuarantine { .. } => "Purge the quarantine",
+                Commands::History { .. } => "Show the history",
+                Commands::Undo { .. } => "Undo a package removal",
+                Commands::Reclaim { .. } => "Reclaim a target amount of space",
+                Commands::Auto { .. } => "Clean up above a usage threshold",
+                Commands::JournalUsage { .. } => "Analyze journal usage",
+                Commands::Analyze { .. } => "Analyze disk usage",
+                Commands::RemovePackage { .. }
+                | Commands::CleanPackageCache
+                | Commands::UninstallUnusedApps
+                | Commands::RemoveOldKernels { .. }
+                | Commands::CleanUpLogFiles { .. }
+                | Commands::HeldSpace { .. }
+                | Commands::CleanDevCaches { .. }
+                | Commands::CleanTempFiles => task::of(self).map_or("", |task| task.title()),
+            }
+        }
+
+        /// Returns `true` if the command only inspects the system and never frees any space.
+        pub fn is_read_only(&self) -> bool {
+            matches!(self, Commands::Analyze { .. } | Commands::JournalUsage { .. } | Commands::History { .. })
+        }
+
+        /// Returns the paths the command frees space below that may be on other filesystems than
+        /// `space::WATCHED_PATHS`, such as the home directory on a separate `/home`.
+        pub fn locations(&self) -> Vec<PathBuf> {
+            match self {
+                Commands::CleanDevCaches { tools, all, roots, .. } => {
+                    let caches = devcache::known_caches(|name| std::env::var_os(name).map(PathBuf::from));
+                    let roots = if searches_projects(tools, *all) { project_roots(roots) } else { Vec::new() };
+                    caches.into_iter().map(|cache| cache.path).chain(roots).collect()
+                }
+                Commands::CargoTargets { roots, .. } => project_roots(roots),
+                Commands::Duplicates { paths, .. } => paths.clone(),
+                _ => Vec::new(),
+            }
+        }
+
+        /// Executes a command based on the selected command variant.
+        ///
+        /// The command is first turned into a `Plan`. In dry-run mode the plan is printed together
+        /// with previews of what it would remove, otherwise it is executed.
+        ///
+        /// Returns the structured result of the command, if it has one, for the JSON report. For
+        /// example, `Analyze` returns the analysis and a dry run returns its plan.
+        ///
+        /// # Arguments
+        ///
+        /// * `self` - A `Commands` enum variant to execute.
+        /// * `session` - The state shared by all commands, such as the package manager backend.
+        ///
+        /// # Errors
+        ///
+        /// Returns an `Error` if any error occurs during command execution.
+        ///
+        /// # Examples
+        ///
+        /// ```no_run
+        /// use clap::Parser;
+        /// use disk_space_optimizer::{
+        ///     cli::{Cli, Commands},
+        ///     session::Session,
+        /// };
+        ///
+        /// let session = Session::from_cli(&Cli::parse())?;
+        /// let command = Commands::CleanPackageCache;
+        /// let result = command.execute(&session);
+        /// assert!(result.is_ok());
+        /// # anyhow::Ok(())
+        /// ```
+        pub fn execute(&self, session: &Session) -> Result<Option<serde_json::Value>, Error> {
+            if let Commands::Analyze { path, one_file_system, top } = self {
+                let options = ScanOptions { one_file_system: *one_file_system, top: *top };
+                let analysis = analyze::scan(path, options)?;
+                print_analysis(&analysis);
+                return Ok(Some(serde_json::to_value(analysis)?));
+            }
+            if let Commands::JournalUsage { since, top } = self {
+                let usage = journal::usage(&session.runner, since.as_deref(), *top)?;
+                print_journal_usage(&usage);
+                return Ok(Some(serde_json::to_value(usage)?));
+            }
+            if let Commands::HeldSpace { action, all } = self {
+                let held = held::scan(Path::new(PROC_DIR));
+                print_held_space(&held);
+                let plan = held_space_plan(self.title(), session, &held, *action, *all)?;
+                run_confirmed(session, &plan, "Proceed to free the held space")?;
+                return Ok(Some(serde_json::to_value(held)?));
+            }
+            if let Commands::CleanDevCaches { tools, all, roots, stale_days } = self {
+                let caches = dev_caches(roots, *stale_days, searches_projects(tools, *all))?;
+                print_dev_caches(&caches);
+                let plan = dev_cache_plan(self.title(), session, &caches, tools, *all)?;
+                run_confirmed(session, &plan, "Proceed to remove the caches")?;
+                return Ok(Some(serde_json::to_value(caches)?));
+            }
+            if let Commands::CargoTargets { roots, older_than, prune } = self {
+                let targets = cargo_targets(roots)?;
+                print_cargo_targets(&targets, SystemTime::now());
+                let plan = cargo_target_plan(self.title(), session, &targets, *older_than, *prune)?;
+                run_confirmed(session, &plan, "Proceed to remove the build output")?;
+                return Ok(Some(serde_json::to_value(targets)?));
+            }
+            if let Commands::Quarantine { paths } = self {
+                return quarantine_paths(session, paths).map(Some);
+            }
+            if let Commands::Restore { ids, all } = self {
+                return restore_quarantined(session, ids, *all).map(Some);
+            }
+            if let Commands::PurgeQuarantine { older_than } = self {
+                return purge_quarantine(session, *older_than).map(Some);
+            }
+            if let Commands::History { since, task, failed, per } = self {
+                let since = since.map(|days| SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60));
+                let filter = history::Filter { since, task: task.clone(), failed: *failed };
+                return show_history(session, &filter, *per).map(Some);
+            }
+            if let Commands::Undo { run_id } = self {
+                let plan = undo_plan(self.title(), session, run_id)?;
+                run_confirmed(session, &plan, "Proceed to reinstall the packages")?;
+                return Ok(Some(serde_json::to_value(plan)?));
+            }
+            if let Commands::Reclaim { target, mount } = self {
+                let outcome = reclaim_space(session, logs::parse_size(target)?, mount)?;
+                return Ok(Some(serde_json::to_value(outcome)?));
+            }
+            if let Commands::Auto { when_used_above, mount } = self {
+                let outcome = auto_clean(session, auto::parse_percent(when_used_above)?, mount)?;
+                return Ok(Some(serde_json::to_value(outcome)?));
+            }
+            if let Commands::Duplicates { paths, action, min_size } = self {
+                let duplicates = find_duplicates(paths, min_size.as_deref())?;
+                print_duplicates(&duplicates);
+                let plan = duplicates_plan(self.title(), session, &duplicates, *action)?;
+                run_confirmed(session, &plan, "Proceed to replace the duplicates")?;
+                return Ok(Some(serde_json::to_value(duplicates)?));
+            }
+
+            let plan = self.plan(session)?;
+            if session.dry_run {
+                plan.print(&session.runner);
+                return Ok(Some(serde_json::to_value(plan)?));
+            }
+
+            match self {
+                Commands::RemovePackage { .. } | Commands::UninstallUnusedApps | Commands::RemoveOldKernels { .. } => {
+                    if plan.steps.is_empty() {
+                        return plan.execute(&session.runner).map(|()| None);
+                    }
+                    say!("These commands will remove packages:");
+                    for step in &plan.steps {
+                        say!("  {step}");
+                    }
+                    if !session.confirm("Proceed to remove the packages")? {
+                        return Err(anyhow!("Aborted deleting selected packages."));
+                    }
+                    let Some(state_dir) = &session.state_dir else {
+                        return plan.execute(&session.runner).map(|()| None);
+                    };
+                    let installed = session.package_manager.list_installed(&session.runner)?;
+                    plan.execute(&session.runner)?;
+                    record_removal(session, state_dir, &plan.task, &installed)?;
+                    Ok(None)
+                }
+                Commands::CleanUpLogFiles { .. } => {
+                    let before = journal::disk_usage(&session.runner)?;
+                    // The first step only vacuums the journal within the given limits. The others
+                    // delete and compress the rotated logs listed above, which need a confirmation.
+                    if plan.steps.len() > 1 {
+                        run_confirmed(session, &plan, "Proceed to clean up the rotated logs")?;
+                    } else {
+                        plan.execute(&session.runner)?;
+                    }
+                    let after = journal::disk_usage(&session.runner)?;
+                    say!("Journal disk usage: {before} before, {after} after");
+                    Ok(Some(serde_json::json!({ "disk_usage_before": before, "disk_usage_after": after })))
+                }
+                _ => {
+                    plan.execute(&session.runner)?;
+                    Ok(None)
+                }
+            }
+        }
+
+        /// Builds the plan of external commands for the selected command variant.
+        ///
+        /// Commands that need a selection, such as the packages or kernels to remove, prompt for
+        /// it here so that the plan is complete before anything runs.
+        ///
+        /// # Errors
+        ///
+        /// Returns an `Error` if querying the system or prompting the user fails, or if nothing was
+        /// selected.
+        pub fn plan(&self, session: &Session) -> Result<Plan, Error> {
+            let package_manager = session.package_manager.as_ref();
+            let runner = &session.runner;
+
+            match self {
+                Commands::RemovePackage { packages } => {
+                    if !packages.is_empty() {
+                        let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+                        let protected = ProtectedKernels::detect(runner, Path::new(BOOT_DIR))?;
+                        return self.removal_plan(session, &protected, packages);
+                    }
+
+                    let mut pkgs_installed = package_manager.list_installed(runner)?;
+                    pkgs_installed.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+                    let mut items: Vec<String> = pkgs_installed.iter().map(describe_package).collect();
+                    items.push(String::from("None"));
+                    let pkgs_selected = session.select(
+                        "Select installed packages to remove (name, version, arch, size, installed, repo):",
+                        &items,
+                        &[],
+                        "Pass the package names as arguments, e.g. `remove-package pkg1 pkg2`.",
+                    )?;
+
+                    if pkgs_selected.is_empty() {
+                        say!("No packages selected. Please try again.");
+                        return Err(anyhow!("No packages were selected"));
+                    } else if pkgs_selected.contains(&pkgs_installed.len()) {
+                        return Err(anyhow!("No packages were selected"));
+                    }
+                    let mut pkgs_selected: Vec<&str> =
+                        pkgs_selected.into_iter().map(|value: usize| pkgs_installed[value].name.as_str()).collect();
+                    // Packages installed for several architectures are listed once per architecture.
+                    pkgs_selected.sort_unstable();
+                    pkgs_selected.dedup();
+
+                    let protected = ProtectedKernels::detect(runner, Path::new(BOOT_DIR))?;
+                    self.removal_plan(session, &protected, pkgs_selected)
+                }
+                Commands::CleanPackageCache => Ok(Plan::new(self.title()).with_step(package_manager.clean_cache())),
+                Commands::UninstallUnusedApps => {
+                    Ok(Plan::new(self.title()).with_optional_step(package_manager.autoremove(runner)?))
+                }
+                Commands::RemoveOldKernels { keep } => {
+                    let mut kernels = package_manager.list_kernels(runner)?;
+                    kernels.sort_by(|a, b| version::rpmvercmp(a, b));
+                    let kernels: Vec<&str> = kernels.iter().map(String::as_str).collect();
+                    let protected = ProtectedKernels::detect(runner, Path::new(BOOT_DIR))?;
+                    let reasons: Vec<_> =
+                        kernels.iter().map(|kernel| protected.reason(package_manager, kernel)).collect();
+
+                    // The kernels are sorted oldest first, so the newest ones are kept at the end.
+                    let retained = |keep: usize| kernels.len().saturating_sub(keep);
+                    let default_keep = session.config.kernels.keep.unwrap_or(DEFAULT_KEPT_KERNELS);
+                    let selected_kernels: Vec<&str> = match keep {
+                        Some(keep) => kernels[..retained(*keep)].to_vec(),
+                        None if !session.interactive => kernels[..retained(default_keep)].to_vec(),
+                        None => {
+                            let items: Vec<String> = kernels
+                                .iter()
+                                .zip(&reasons)
+                                .map(|(kernel, reason)| match reason {
+                                    Some(reason) => format!("{kernel} (protected: {reason})"),
+                                    None => kernel.to_string(),
+                                })
+                                .collect();
+                            let defaults: Vec<bool> = reasons
+                                .iter()
+                                .enumerate()
+                                .map(|(i, reason)| i < retained(default_keep) && reason.is_none())
+                                .collect();
+                            let selected = session.select(
+                                "Select kernel versions to remove:",
+                                &items,
+                                &defaults,
+                                "Pass --keep <N> to keep the newest N kernels and remove the rest.",
+                            )?;
+                            selected.into_iter().map(|i| kernels[i]).collect()
+                        }
+                    };
+
+                    if selected_kernels.is_empty() {
+                        say!("No kernels selected.");
+                        return Ok(Plan::new(self.title()));
+                    }
+
+                    self.removal_plan(session, &protected, selected_kernels)
+                }
+                Commands::CleanUpLogFiles {
+                    vacuum_time,
+                    vacuum_size,
+                    vacuum_files,
+                    rotate,
+                    log_max_age,
+                    log_max_size,
+                    no_compress,
+                    journal_only,
+                } => {
+                    let journal = &session.config.journal;
+                    let vacuum_size = vacuum_size.as_ref().or(journal.vacuum_size.as_ref());
+                    let vacuum_files = vacuum_files.or(journal.vacuum_files);
+                    let vacuum_time = match vacuum_time.or(journal.vacuum_time) {
+                        Some(days) => Some(days),
+                        // A size or file limit is enough on its own.
+                        None if vacuum_size.is_some() || vacuum_files.is_some() => None,
+                        None => Some(session.ask(
+                            &format!("Enter vacuum time (Default: {DEFAULT_VACUUM_DAYS}) as days:"),
+                            DEFAULT_VACUUM_DAYS,
+                        )?),
+                    };
+
+                    let mut args = vec![String::from("journalctl")];
+                    if *rotate {
+                        args.push(String::from("--rotate"));
+                    }
+                    args.extend(vacuum_time.map(|days| format!("--vacuum-time={days}d")));
+                    args.extend(vacuum_size.map(|size| format!("--vacuum-size={size}")));
+                    args.extend(vacuum_files.map(|files| format!("--vacuum-files={files}")));
+                    let step = Step::sudo(&args).with_preview(&["journalctl", "--disk-usage"]);
+                    let mut plan = Plan::new(self.title()).with_step(step);
+                    if *journal_only {
+                        return Ok(plan);
+                    }
+
+                    let config = &session.config.logs;
+                    let policy = logs::Policy {
+                        max_age: Duration::from_secs(
+                            log_max_age.or(config.max_age).unwrap_or(logs::DEFAULT_MAX_AGE_DAYS) * 24 * 60 * 60,
+                        ),
+                        max_size: log_max_size
+                            .as_ref()
+                            .or(config.max_size.as_ref())
+                            .map(|size| logs::parse_size(size))
+                            .transpose()?,
+                        compress: !no_compress && config.compress.unwrap_or(true),
+                    };
+                    let log_dir = config.directory.as_deref().unwrap_or(Path::new(logs::LOG_DIR));
+                    plan.steps.extend(rotated_log_steps(log_dir, policy, session.quarantine));
+                    Ok(plan)
+                }
+                Commands::HeldSpace { action, all } => {
+                    held_space_plan(self.title(), session, &held::scan(Path::new(PROC_DIR)), *action, *all)
+                }
+                Commands::CleanDevCaches { tools, all, roots, stale_days } => {
+                    let caches = dev_caches(roots, *stale_days, searches_projects(tools, *all))?;
+                    dev_cache_plan(self.title(), session, &caches, tools, *all)
+                }
+                Commands::CleanTempFiles => Ok(Plan::new(self.title()).with_step(
+                    Step::sudo(&["systemd-tmpfiles", "--clean"]).with_preview(&["du", "-sh", "/tmp", "/var/tmp"]),
+                )),
+                Commands::CargoTargets { roots, older_than, prune } => {
+                    cargo_target_plan(self.title(), session, &cargo_targets(roots)?, *older_than, *prune)
+                }
+                Commands::Duplicates { paths, action, min_size } => {
+                    duplicates_plan(self.title(), session, &find_duplicates(paths, min_size.as_deref())?, *action)
+                }
+                // These manage the quarantine themselves rather than through external commands.
+                Commands::Quarantine { .. } | Commands::Restore { .. } | Commands::PurgeQuarantine { .. } => {
+                    Ok(Plan::new(self.title()))
+                }
+                Commands::Undo { run_id } => undo_plan(self.title(), session, run_id),
+                // The tasks of the plan are only chosen while it runs, since it stops at the target.
+                Commands::Reclaim { .. } => Ok(Plan::new(self.title())),
+                // Whether any task runs depends on the usage when the command runs.
+                Commands::Auto { .. } => Ok(Plan::new(self.title())),
+                // Analyzing only reads the filesystem and the journal and changes nothing.
+                Commands::Analyze { .. } | Commands::JournalUsage { .. } | Commands::History { .. } => {
+                    Ok(Plan::new(self.title()))
+                }
+            }
+        }
+
+        /// Builds the plan that removes `packages`, leaving out the protected kernels and the
+        /// packages protected in the configuration.
+        fn removal_plan(&self, session: &Session, protected: &ProtectedKernels, packages: Vec<&str>) -> Result<Plan> {
+            let package_manager = session.package_manager.as_ref();
+            let protected_packages = &session.config.packages.protected;
+            let packages = protected
+                .filter(package_manager, packages)
+                .into_iter()
+                .filter(|package| {
+                    let is_protected = protected_packages.iter().any(|protected| protected == package);
+                    if is_protected {
+                        say!("Refusing to remove {package}: it is protected in the configuration.");
+                    }
+                    !is_protected
+                })
+                .collect::<Vec<_>>();
+            if packages.is_empty() {
+                return Ok(Plan::new(self.title()));
+            }
+            Ok(Plan::new(self.title()).with_step(package_manager.remove(&packages)))
+        }
+    }
+
+    /// Prints `plan` in dry-run mode. Otherwise lists its steps and executes them once the user
+    /// confirms `prompt`.
+    fn run_confirmed(session: &Session, plan: &Plan, prompt: &str) -> Result<()> {
+        if session.dry_run {
+            plan.print(&session.runner);
+            return Ok(());
+        }
+        if plan.steps.is_empty() {
+            return Ok(());
+        }
+        for step in &plan.steps {
+            say!("  {step}");
+        }
+        if !session.confirm(prompt)? {
+            return Err(anyhow!("Aborted: {task}.", task = plan.task));
+        }
+        plan.execute(&session.runner)
+    }
+
+    /// Returns `roots`, or the current directory when empty, as the searches for projects do.
+    fn project_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+        if roots.is_empty() {
+            std::env::current_dir().into_iter().collect()
+        } else {
+            roots.to_vec()
+        }
+    }
+
+    /// Returns `true` if `clean-dev-caches` with `tools` and `all` may remove project directories,
+    /// so that they must be searched for.
+    fn searches_projects(tools: &[String], all: bool) -> bool {
+        all || tools.is_empty()
+            || devcache::PROJECT_TOOLS.iter().any(|project| tools.contains(&project.name.to_string()))
+    }
+
+    /// Finds and measures the developer caches. With `projects`, they include the project
+    /// directories below `roots` (the current directory when empty) not modified for `stale_days`
+    /// days.
+    fn dev_caches(roots: &[PathBuf], stale_days: u64, projects: bool) -> Result<Vec<devcache::ToolCaches>> {
+        let mut caches = devcache::known_caches(|name| std::env::var_os(name).map(PathBuf::from));
+        if projects {
+            let roots = if roots.is_empty() { vec![std::env::current_dir()?] } else { roots.to_vec() };
+            let stale_after = Duration::from_secs(stale_days * 24 * 60 * 60);
+            caches.extend(devcache::find_project_caches(&roots, stale_after, SystemTime::now()));
+        }
+        Ok(devcache::measure(&caches))
+    }
+
+    /// Builds the plan that removes the caches of the selected `tools`, of every tool with `all`,
+    /// or of the tools picked in a prompt. Without a terminal, the caches are only listed.
+    fn dev_cache_plan(
+        title: &str,
+        session: &Session,
+        caches: &[devcache::ToolCaches],
+        tools: &[String],
+        all: bool,
+    ) -> Result<Plan> {
+        let selected: Vec<&devcache::ToolCaches> = if all {
+            caches.iter().collect()
+        } else if !tools.is_empty() {
+            caches.iter().filter(|cache| tools.iter().any(|tool| tool == cache.tool)).collect()
+        } else if !session.interactive || caches.is_empty() {
+            return Ok(Plan::new(title));
+        } else {
+            let items = multidialogue::DiskSpaceOptimizerItems::new().with_options(
+                caches
+                    .iter()
+                    .map(|cache| {
+                        let text = format!("{label} ({bytes})", label = cache.label, bytes = format_bytes(cache.bytes));
+                        multidialogue::SelectableItem::new(cache.tool, &text)
+                    })
+                    .collect(),
+            );
+            let selected = session.select(
+                "Select the caches to remove:",
+                &multidialogue::format_prompt_item(&items)?,
+                &[],
+                "Pass --tool <NAME> or --all.",
+            )?;
+            selected.into_iter().map(|i| &caches[i]).collect()
+        };
+
+        let mut plan = Plan::new(title);
+        for cache in selected {
+            if !cache.read_only.is_empty() {
+                let mut args = vec![String::from("-R"), String::from("u+w"), String::from("--")];
+                args.extend(cache.read_only.iter().map(|path| path.to_string_lossy().into_owned()));
+                plan = plan.with_step(Step::new("chmod", &args));
+            }
+            plan = plan.with_step(session.remove_step(&cache.paths));
+        }
+        Ok(plan)
+    }
+
+    /// Prints the reclaimable size of the developer caches per tool.
+    fn print_dev_caches(caches: &[devcache::ToolCaches]) {
+        if caches.is_empty() {
+            say!("No developer caches found.");
+            return;
+        }
+        say!("Developer caches:");
+        for cache in caches {
+            say!(
+                "  {bytes:>10}  {tool:<14} {label}",
+                bytes = format_bytes(cache.bytes),
+                tool = cache.tool,
+                label = cache.label
+            );
+        }
+    }
+
+    /// Finds the Cargo target directories below `roots`, or below the current directory when empty.
+    fn cargo_targets(roots: &[PathBuf]) -> Result<Vec<targets::TargetDir>> {
+        let roots = if roots.is_empty() { vec![std::env::current_dir()?] } else { roots.to_vec() };
+        Ok(targets::find(&roots))
+    }
+
+    /// Builds the plan that removes the build output not touched for `older_than_days` days, see
+    /// `targets::select`. With a terminal, the user picks which projects to clean up.
+    fn cargo_target_plan(
+        title: &str,
+        session: &Session,
+        targets: &[targets::TargetDir],
+        older_than_days: u64,
+        prune: bool,
+    ) -> Result<Plan> {
+        let older_than = Duration::from_secs(older_than_days * 24 * 60 * 60);
+        let cleanups = targets::select(targets, older_than, prune, SystemTime::now());
+        let mut plan = Plan::new(title);
+        if cleanups.is_empty() {
+            say!("No build output older than {older_than_days} days.");
+            return Ok(plan);
+        }
+
+        let selected: Vec<&targets::Cleanup> = if session.interactive {
+            let items: Vec<String> = cleanups
+                .iter()
+                .map(|cleanup| format!("{:>10}  {}", format_bytes(cleanup.bytes), cleanup.project.display()))
+                .collect();
+            let selected = session.select(
+                "Select the projects to clean up:",
+                &items,
+                &vec![true; items.len()],
+                "Pass --older-than <DAYS> to choose by age.",
+            )?;
+            selected.into_iter().map(|i| &cleanups[i]).collect()
+        } else {
+            cleanups.iter().collect()
+        };
+        for cleanup in selected {
+            plan = plan.with_step(session.remove_step(&cleanup.paths));
+        }
+        Ok(plan)
+    }
+
+    /// Prints the Cargo target directories with their size and the time of their last build.
+    fn print_cargo_targets(targets: &[targets::TargetDir], now: SystemTime) {
+        if targets.is_empty() {
+            say!("No Cargo target directories found.");
+            return;
+        }
+        say!("Cargo target directories:");
+        for target in targets {
+            let days = now.duration_since(target.last_build).unwrap_or_default().as_secs() / (24 * 60 * 60);
+            say!(
+                "  {bytes:>10}  built {days:>4} days ago  {project}",
+                bytes = format_bytes(target.bytes),
+                project = target.project.display()
+            );
+        }
+    }
+
+    /// What `duplicates` does with the extra copies of a file.
+    #[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DuplicateAction {
+        /// Delete the extra copies.
+        Delete,
+        /// Replace the extra copies with hard links to the kept copy, on the same filesystem only.
+        Hardlink,
+        /// Replace the extra copies with reflinks to the kept copy, which share its data but stay
+        /// independent files. Only supported on btrfs and xfs.
+        Reflink,
+    }
+
+    /// Finds the duplicate files below `paths`, ignoring files smaller than `min_size`.
+    fn find_duplicates(paths: &[PathBuf], min_size: Option<&str>) -> Result<duplicates::Duplicates> {
+        let min_size = min_size.map(logs::parse_size).transpose()?.unwrap_or(1);
+        Ok(duplicates::find(paths, min_size))
+    }
+
+    /// Builds the plan that deals with the extra copies of each set of duplicates.
+    ///
+    /// With `action`, the first copy of each set is kept. Otherwise the user picks the copies to
+    /// keep and the action for each set. Without a terminal, the duplicates are only listed.
+    fn duplicates_plan(
+        title: &str,
+        session: &Session,
+        duplicates: &duplicates::Duplicates,
+        action: Option<DuplicateAction>,
+    ) -> Result<Plan> {
+        let mut plan = Plan::new(title);
+        if action.is_none() && !session.interactive {
+            return Ok(plan);
+        }
+
+        for (n, set) in duplicates.sets.iter().enumerate() {
+            let (kept, action): (Vec<&Path>, DuplicateAction) = match action {
+                Some(action) => (vec![&set.paths[0]], action),
+                None => {
+                    say!(
+                        "Set {n} of {total}: {count} copies of {bytes}",
+                        n = n + 1,
+                        total = duplicates.sets.len(),
+                        count = set.paths.len(),
+                        bytes = format_bytes(set.bytes)
+                    );
+                    let items = multidialogue::DiskSpaceOptimizerItems::new().with_options(
+                        set.paths
+                            .iter()
+                            .enumerate()
+                            .map(|(i, path)| multidialogue::SelectableItem::new(i + 1, &path.to_string_lossy()))
+                            .collect(),
+                    );
+                    let defaults: Vec<bool> = (0..set.paths.len()).map(|i| i == 0).collect();
+                    let kept = session.select(
+                        "Select the copies to keep:",
+                        &multidialogue::format_prompt_item(&items)?,
+                        &defaults,
+                        "Pass --action to keep the first copy of every set.",
+                    )?;
+                    let answer: String = session.ask(
+                        "(d)elete the other copies, replace them with (h)ard links or (r)eflinks, or (s)kip?",
+                        String::from("s"),
+                    )?;
+                    let action = match answer.as_str() {
+                        "d" => DuplicateAction::Delete,
+                        "h" => DuplicateAction::Hardlink,
+                        "r" => DuplicateAction::Reflink,
+                        _ => continue,
+                    };
+                    (kept.into_iter().map(|i| set.paths[i].as_path()).collect(), action)
+                }
+            };
+
+            let Some(source) = kept.first() else {
+                say!("Skipping set {n}: at least one copy must be kept.", n = n + 1);
+                continue;
+            };
+            let source = source.to_string_lossy();
+            let extras: Vec<PathBuf> =
+                set.paths.iter().filter(|path| !kept.contains(&path.as_path())).cloned().collect();
+            match action {
+                DuplicateAction::Delete if !extras.is_empty() => plan = plan.with_step(session.remove_step(&extras)),
+                DuplicateAction::Delete => {}
+                DuplicateAction::Hardlink => {
+                    let device = |path: &Path| std::fs::metadata(path).map(|metadata| metadata.dev()).ok();
+                    for extra in &extras {
+                        if device(extra) != device(Path::new(source.as_ref())) {
+                            say!("Skipping {extra}: hard links cannot cross filesystems.", extra = extra.display());
+                            continue;
+                        }
+                        plan = plan.with_step(Step::new("ln", &["-f", "--", &source, &extra.to_string_lossy()]));
+                    }
+                }
+                DuplicateAction::Reflink => {
+                    if !duplicates::supports_reflink(Path::new(source.as_ref())) {
+                        say!("Skipping set {n}: the filesystem does not support reflinks.", n = n + 1);
+                        continue;
+                    }
+                    for extra in &extras {
+                        let extra = extra.to_string_lossy();
+                        plan = plan.with_step(Step::new("cp", &["--reflink=always", "-f", "--", &source, &extra]));
+                    }
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Prints the sets of duplicate files with the space each of them wastes.
+    fn print_duplicates(duplicates: &duplicates::Duplicates) {
+        if duplicates.errors > 0 {
+            say!("{count} files or directories could not be read.", count = duplicates.errors);
+        }
+        if duplicates.sets.is_empty() {
+            say!("No duplicates among {count} files.", count = duplicates.files);
+            return;
+        }
+        say!(
+            "{sets} sets of duplicates among {count} files, wasting {bytes}:",
+            sets = duplicates.sets.len(),
+            count = duplicates.files,
+            bytes = format_bytes(duplicates.wasted)
+        );
+        for set in &duplicates.sets {
+            say!(
+                "  {wasted:>10}  {count} copies of {bytes}",
+                wasted = format_bytes(set.wasted()),
+                count = set.paths.len(),
+                bytes = format_bytes(set.bytes)
+            );
+            for path in &set.paths {
+                say!("      {path}", path = path.display());
+            }
+        }
+    }
+
+    /// Moves `paths` into the quarantine, returning the new entries.
+    fn quarantine_paths(session: &Session, paths: &[PathBuf]) -> Result<serde_json::Value> {
+        let user_dir = quarantine::user_dir();
+        let mut entries = Vec::new();
+        for path in paths {
+            let dir = quarantine::dir_for(path, user_dir.as_deref())?;
+            if session.dry_run {
+                say!("Dry run: would move {path} to {dir}", path = path.display(), dir = dir.display());
+                continue;
+            }
+            let entry = quarantine::quarantine(path, &dir, SystemTime::now())?;
+            say!("Quarantined {path} as {id}", path = path.display(), id = entry.id);
+            entries.push(entry);
+        }
+        Ok(serde_json::to_value(entries)?)
+    }
+
+    /// Restores the quarantined items with the given `ids`, or all of them. Without either, the
+    /// quarantine is listed, and with a terminal the user picks the items to restore.
+    fn restore_quarantined(session: &Session, ids: &[String], all: bool) -> Result<serde_json::Value> {
+        let entries = quarantine::list(&quarantine::dirs());
+        for id in ids {
+            if !entries.iter().any(|entry| &entry.id == id) {
+                return Err(anyhow!("No quarantined item with ID {id}. Run `restore` to list them."));
+            }
+        }
+        let selected: Vec<&quarantine::Entry> = if all {
+            entries.iter().collect()
+        } else if !ids.is_empty() {
+            entries.iter().filter(|entry| ids.contains(&entry.id)).collect()
+        } else {
+            print_quarantine(&entries);
+            if !session.interactive || entries.is_empty() {
+                return Ok(serde_json::to_value(&entries)?);
+            }
+            let items: Vec<String> = entries.iter().map(describe_quarantined).collect();
+            let selected =
+                session.select("Select the items to restore:", &items, &[], "Pass the IDs as arguments, or --all.")?;
+            selected.into_iter().map(|i| &entries[i]).collect()
+        };
+
+        for entry in &selected {
+            let path = entry.manifest.original_path.display();
+            if session.dry_run {
+                say!("Dry run: would restore {path}");
+                continue;
+            }
+            quarantine::restore(entry)?;
+            say!("Restored {path}");
+        }
+        Ok(serde_json::to_value(selected)?)
+    }
+
+    /// Deletes the items quarantined more than `older_than_days` days ago, once the user confirms.
+    fn purge_quarantine(session: &Session, older_than_days: u64) -> Result<serde_json::Value> {
+        let entries = quarantine::list(&quarantine::dirs());
+        let older_than = Duration::from_secs(older_than_days * 24 * 60 * 60);
+        let expired = quarantine::expired(&entries, older_than, SystemTime::now());
+        if expired.is_empty() {
+            say!("No quarantined items older than {older_than_days} days.");
+            return Ok(serde_json::to_value(expired)?);
+        }
+
+        let bytes = expired.iter().map(|entry| entry.manifest.bytes).sum();
+        say!("These items will be deleted, freeing {bytes}:", bytes = format_bytes(bytes));
+        for entry in &expired {
+            say!("  {}", describe_quarantined(entry));
+        }
+        if session.dry_run {
+            return Ok(serde_json::to_value(expired)?);
+        }
+        if !session.confirm("Proceed to purge the quarantine")? {
+            return Err(anyhow!("Aborted: {task}.", task = "Purge the quarantine"));
+        }
+        for entry in &expired {
+            quarantine::purge(entry)?;
+        }
+        Ok(serde_json::to_value(expired)?)
+    }
+
+    /// Describes a quarantined item in a single line, e.g. `1700000000-42-0  1.5 GiB  /var/log/app.log`.
+    fn describe_quarantined(entry: &quarantine::Entry) -> String {
+        format!(
+            "{id}  {bytes:>10}  {path}",
+            id = entry.id,
+            bytes = format_bytes(entry.manifest.bytes),
+            path = entry.manifest.original_path.display()
+        )
+    }
+
+    /// Prints the quarantined items, oldest first.
+    fn print_quarantine(entries: &[quarantine::Entry]) {
+        if entries.is_empty() {
+            say!("The quarantine is empty.");
+            return;
+        }
+        let bytes = entries.iter().map(|entry| entry.manifest.bytes).sum();
+        say!("{count} quarantined items, taking up {bytes}:", count = entries.len(), bytes = format_bytes(bytes));
+        for entry in entries {
+            say!("  {}", describe_quarantined(entry));
+        }
+    }
+
+    /// Describes an installed package in a single line of aligned columns, e.g.
+    /// `firefox  115.0-1.fc38  x86_64  250.3 MiB  2023-05-01  updates`.
+    fn describe_package(package: &Package) -> String {
+        let installed = package.installed.map(history::format_time).unwrap_or_default();
+        format!(
+            "{name:<40} {version:<28} {arch:<8} {size:>10}  {installed:<10}  {repo}",
+            name = package.name,
+            version = package.version,
+            arch = package.arch,
+            size = format_bytes(package.size),
+            installed = installed.split(' ').next().unwrap_or_default(),
+            repo = package.repo.as_deref().unwrap_or("-"),
+        )
+    }
+
+    /// Returns the directory of the removal history, or an error if it is unknown.
+    fn history_dir(session: &Session) -> Result<PathBuf> {
+        session
+            .state_dir
+            .clone()
+            .ok_or_else(|| anyhow!("The history is unavailable: neither XDG_STATE_HOME nor HOME is set."))
+    }
+
+    /// Records the packages that a removal of `task` took away from the `installed` ones in the
+    /// removal history in `state_dir`, so that `undo` can bring them back.
+    fn record_removal(session: &Session, state_dir: &Path, task: &str, installed: &[Package]) -> Result<()> {
+        let package_manager = session.package_manager.as_ref();
+        let packages = history::removed_packages(installed, &package_manager.list_installed(&session.runner)?);
+        if packages.is_empty() {
+            return Ok(());
+        }
+        let removal = Removal {
+            run_id: session.run_id.clone(),
+            time: SystemTime::now(),
+            backend: package_manager.name().to_string(),
+            task: task.to_string(),
+            packages,
+            transaction: package_manager.last_transaction(&session.runner)?,
+        };
+        history::record(state_dir, &removal)?;
+        say!("Recorded as run {run_id}. Undo it with `undo {run_id}`.", run_id = removal.run_id);
+        Ok(())
+    }
+
+    /// Builds the plan that reinstalls the packages removed by the run `run_id`, undoing its
+    /// removals in reverse order.
+    fn undo_plan(task: &str, session: &Session, run_id: &str) -> Result<Plan> {
+        let removals = history::removals(&history_dir(session)?)?;
+        let removals: Vec<&Removal> = removals.iter().filter(|removal| removal.run_id == run_id).collect();
+        if removals.is_empty() {
+            return Err(anyhow!("No removals recorded for run {run_id}. Run `history` to list them."));
+        }
+
+        let package_manager = session.package_manager.as_ref();
+        let mut plan = Plan::new(task);
+        for removal in removals.into_iter().rev() {
+            if removal.backend != package_manager.name() {
+                return Err(anyhow!(
+                    "Run {run_id} removed packages with {backend}, not {current}.",
+                    backend = removal.backend,
+                    current = package_manager.name()
+                ));
+            }
+            let packages: Vec<&str> = removal.packages.iter().map(String::as_str).collect();
+            plan.steps.push(package_manager.undo(removal.transaction.as_deref(), &packages));
+        }
+        Ok(plan)
+    }
+
+    /// Prints the runs in the history matching `filter`, newest first, with the packages they
+    /// removed, followed by the space reclaimed per task and `period`.
+    fn show_history(session: &Session, filter: &history::Filter, period: Period) -> Result<serde_json::Value> {
+        let state_dir = history_dir(session)?;
+        let mut runs = filter.apply(history::runs(&state_dir)?);
+        runs.reverse();
+        let removals: Vec<Removal> = history::removals(&state_dir)?
+            .into_iter()
+            .filter(|removal| {
+                runs.iter()
+                    .any(|run| run.run_id == removal.run_id && run.tasks.iter().any(|task| task.task == removal.task))
+            })
+            .collect();
+        let summary = history::summarize(&runs, period);
+
+        print_runs(&runs, &removals);
+        print_summary(&summary);
+        Ok(serde_json::json!({ "runs": runs, "removals": removals, "summary": summary }))
+    }
+
+    /// Prints `runs` with their tasks, and `removals` below the tasks that made them.
+    fn print_runs(runs: &[Run], removals: &[Removal]) {
+        if runs.is_empty() {
+            say!("No runs recorded.");
+            return;
+        }
+        for run in runs {
+            let mut notes = Vec::new();
+            if run.menu {
+                notes.push("menu");
+            }
+            if run.dry_run {
+                notes.push("dry run");
+            }
+            let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+            say!("{run_id}  {time}{notes}", run_id = run.run_id, time = history::format_time(run.time));
+            for task in &run.tasks {
+                let failed = task.commands.iter().filter(|command| command.exit_code != Some(0)).count();
+                let mut outcome = format!("{count} commands", count = task.commands.len());
+                if failed > 0 {
+                    outcome.push_str(&format!(", {failed} failed"));
+                }
+                if let Some(bytes) = task.bytes_reclaimed {
+                    outcome.push_str(&format!(", {} reclaimed", format_signed_bytes(bytes)));
+                }
+                say!("  {task}: {outcome}", task = task.task);
+                for error in &task.errors {
+                    say!("    Error: {error}");
+                }
+                for removal in
+                    removals.iter().filter(|removal| removal.run_id == run.run_id && removal.task == task.task)
+                {
+                    let transaction =
+                        removal.transaction.as_ref().map(|id| format!(" in transaction {id}")).unwrap_or_default();
+                    say!("    Removed {packages}{transaction}", packages = removal.packages.join(" "));
+                    say!("    Undo with `undo {run_id}`", run_id = run.run_id);
+                }
+            }
+        }
+    }
+
+    /// Prints the space reclaimed per task and period.
+    fn print_summary(summary: &[history::TaskSummary]) {
+        if summary.is_empty() {
+            return;
+        }
+        say!("Reclaimed space per task:");
+        for row in summary {
+            say!(
+                "  {period}  {task}: {bytes} in {runs} runs",
+                period = row.period,
+                task = row.task,
+                bytes = format_signed_bytes(row.bytes_reclaimed),
+                runs = row.runs,
+            );
+        }
+    }
+
+    /// Frees `target` bytes on the filesystem of `mount` with the tasks planned by `reclaim::plan`,
+    /// out of the enabled tasks that can run unattended and free space on that filesystem.
+    ///
+    /// The plan is shown and, once the user approves it, its tasks run without asking anything
+    /// else until the free space has grown by `target`. A task that fails is reported and the next
+    /// one runs. In dry-run mode, every task of the plan prints its own plan instead.
+    fn reclaim_space(session: &Session, target: u64, mount: &Path) -> Result<reclaim::Outcome> {
+        let device = space::device(mount).ok_or_else(|| anyhow!("Failed to read {}", mount.display()))?;
+        let tasks: Vec<&dyn task::Task> = task::TASKS
+            .iter()
+            .copied()
+            .filter(|task| session.config.is_enabled(task.id()) && task.unattended(session).is_some())
+            .filter(|task| task.location(session).is_none_or(|location| space::device(&location) == Some(device)))
+            .collect();
+        say!("Estimating the reclaimable space...");
+        let candidates = tasks
+            .iter()
+            .zip(task::estimates(session, &tasks))
+            .filter_map(|(task, estimate)| {
+                Some(reclaim::Candidate { id: task.id(), title: task.title(), risk: task.risk(), estimate: estimate? })
+            })
+            .collect();
+
+        let free_before = space::statvfs(mount)?.free;
+        let mut outcome = reclaim::Outcome {
+            mount: mount.to_path_buf(),
+            target,
+            plan: reclaim::plan(candidates, target),
+            ran: Vec::new(),
+            free_before,
+            free_after: free_before,
+            reached: false,
+        };
+        if outcome.plan.is_empty() {
+            say!("Nothing to reclaim on {mount}.", mount = mount.display());
+            return Ok(outcome);
+        }
+        say!("Plan to free {target} on {mount}:", target = format_bytes(target), mount = mount.display());
+        for candidate in &outcome.plan {
+            say!(
+                "  {title} ({risk} risk): ~{estimate}",
+                title = candidate.title,
+                risk = candidate.risk,
+                estimate = format_bytes(candidate.estimate)
+            );
+        }
+        let planned: u64 = outcome.plan.iter().map(|candidate| candidate.estimate).sum();
+        if planned < target {
+            say!("These tasks are estimated to free {planned}, less than the target.", planned = format_bytes(planned));
+        }
+        if !session.dry_run {
+            if !session.confirm("Proceed with this plan")? {
+                return Err(anyhow!("Aborted: reclaiming space."));
+            }
+            session.approve();
+        }
+
+        let goal = free_before.saturating_add(target);
+        for candidate in &outcome.plan {
+            let Some(command) = task::by_id(candidate.id).and_then(|task| task.unattended(session)) else {
+                continue;
+            };
+            say!("{title}:", title = candidate.title);
+            if let Err(err) = command.execute(session) {
+                say!("Error: {err:#}");
+            }
+            outcome.ran.push(candidate.id);
+            outcome.free_after = space::statvfs(mount)?.free;
+            if !session.dry_run && outcome.free_after >= goal {
+                outcome.reached = true;
+                break;
+            }
+        }
+
+        if !session.dry_run {
+            let freed = format_signed_bytes(outcome.free_after as i64 - free_before as i64);
+            if outcome.reached {
+                say!("Reached the target: freed {freed} on {mount}.", mount = mount.display());
+            } else {
+                say!("Freed {freed} of {target} on {mount}.", target = format_bytes(target), mount = mount.display());
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Runs the tasks configured under `[auto] tasks`, or `auto::DEFAULT_TASKS`, when the usage of
+    /// the filesystem of `mount` is above `threshold` percent. Tasks that are not low-risk or not
+    /// enabled in the configuration are skipped, and the others run without asking anything. A
+    /// task that fails is reported and the next one runs. In dry-run mode, the tasks print their
+    /// plans and the usage is not measured again.
+    fn auto_clean(session: &Session, threshold: f64, mount: &Path) -> Result<auto::Outcome> {
+        let used_before = auto::used_percent(space::statvfs(mount)?);
+        let mut outcome = auto::Outcome {
+            mount: mount.to_path_buf(),
+            threshold,
+            used_before,
+            used_after: used_before,
+            tasks: Vec::new(),
+            failed: Vec::new(),
+            status: auto::Status::NothingToDo,
+        };
+        if used_before <= threshold {
+            say!("{mount} is {used_before:.1}% full, not above {threshold}%: nothing to do.", mount = mount.display());
+            return Ok(outcome);
+        }
+        say!("{mount} is {used_before:.1}% full, above {threshold}%: cleaning up.", mount = mount.display());
+
+        let ids = match &session.config.auto.tasks {
+            Some(tasks) => tasks.clone(),
+            None => auto::DEFAULT_TASKS.map(String::from).to_vec(),
+        };
+        session.approve();
+        for id in ids {
+            let Some(task) = task::by_id(&id) else {
+                say!("Skipping {id}: there is no such task.");
+                continue;
+            };
+            if !session.config.is_enabled(&id) {
+                say!("Skipping {id}: it is not enabled under [tasks] in the configuration.");
+                continue;
+            }
+            if task.risk() != task::Risk::Low {
+                say!(
+                    "Skipping {id}: only low-risk tasks run automatically, and it is {risk} risk.",
+                    risk = task.risk()
+                );
+                continue;
+            }
+            let Some(command) = task.unattended(session) else {
+                say!("Skipping {id}: it only runs with a selection.");
+                continue;
+            };
+            say!("{title}:", title = task.title());
+            match command.execute(session) {
+                Ok(_) => outcome.tasks.push(id),
+                Err(err) => {
+                    say!("Error: {err:#}");
+                    outcome.failed.push(id);
+                }
+            }
+        }
+
+        if session.dry_run {
+            outcome.status = auto::Status::DryRun;
+            return Ok(outcome);
+        }
+        outcome.used_after = auto::used_percent(space::statvfs(mount)?);
+        outcome.status = if outcome.used_after > threshold { auto::Status::StillAbove } else { auto::Status::Cleaned };
+        say!(
+            "{mount} is {used_after:.1}% full after cleaning up, {relation} {threshold}%.",
+            mount = mount.display(),
+            used_after = outcome.used_after,
+            relation = if outcome.status == auto::Status::Cleaned { "not above" } else { "still above" },
+        );
+        Ok(outcome)
+    }
+
+    /// How `held-space` frees the space of a deleted file.
+    #[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum HeldAction {
+        /// Truncate the file to zero bytes through the descriptor of the process holding it.
+        Truncate,
+        /// Restart the systemd unit of the process holding the file.
+        Restart,
+    }
+
+    /// Builds the plan that frees the space of the held files the user selects, using `action`.
+    ///
+    /// Without an action, the user is asked for one. Without a terminal, the files are only listed.
+    fn held_space_plan(
+        title: &str,
+        session: &Session,
+        held: &held::HeldSpace,
+        action: Option<HeldAction>,
+        all: bool,
+    ) -> Result<Plan> {
+        let mut plan = Plan::new(title);
+        if held.files.is_empty() {
+            return Ok(plan);
+        }
+
+        let action = match action {
+            Some(action) => action,
+            None if !session.interactive => return Ok(plan),
+            None => {
+                let answer: String = session
+                    .ask("Free the space by (t)runcating the files or (r)estarting their units?", String::new())?;
+                match answer.as_str() {
+                    "t" => HeldAction::Truncate,
+                    "r" => HeldAction::Restart,
+                    _ => return Ok(plan),
+                }
+            }
+        };
+
+        let selected: Vec<&held::HeldFile> = if all {
+            held.files.iter().collect()
+        } else {
+            let items: Vec<String> = held.files.iter().map(describe_held_file).collect();
+            let selected =
+                session.select("Select the files to free:", &items, &[], "Pass --all to free every held file.")?;
+            selected.into_iter().map(|i| &held.files[i]).collect()
+        };
+
+        match action {
+            HeldAction::Truncate => {
+                for file in selected {
+                    let fd_path = file.fd_path(Path::new(PROC_DIR));
+                    plan = plan.with_step(Step::sudo(&["truncate", "--size=0", &fd_path.to_string_lossy()]));
+                }
+            }
+            HeldAction::Restart => {
+                let mut units: Vec<&str> = Vec::new();
+                for file in selected {
+                    match &file.unit {
+                        Some(unit) if !units.contains(&unit.as_str()) => units.push(unit),
+                        Some(_) => {}
+                        None => say!(
+                            "Skipping {path}: process {pid} ({process}) does not belong to a systemd service",
+                            path = file.path.display(),
+                            pid = file.pid,
+                            process = file.process
+                        ),
+                    }
+                }
+                for unit in units {
+                    plan = plan.with_step(Step::sudo(&["systemctl", "restart", unit]));
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Describes a held file in a single line, e.g. `1.5 GiB  /var/log/app.log  nginx (1234, nginx.service)`.
+    fn describe_held_file(file: &held::HeldFile) -> String {
+        format!(
+            "{bytes:>10}  {path}  {process} ({pid}{unit})",
+            bytes = format_bytes(file.bytes),
+            path = file.path.display(),
+            process = file.process,
+            pid = file.pid,
+            unit = file.unit.as_ref().map(|unit| format!(", {unit}")).unwrap_or_default()
+        )
+    }
+
+    /// Prints the deleted files that are still held open.
+    fn print_held_space(held: &held::HeldSpace) {
+        if held.inaccessible > 0 {
+            say!(
+                "The open files of {count} processes cannot be inspected. Run as root to include them.",
+                count = held.inaccessible
+            );
+        }
+        if held.files.is_empty() {
+            say!("No deleted files are held open.");
+            return;
+        }
+        say!(
+            "{count} deleted files are held open, pinning {bytes}:",
+            count = held.files.len(),
+            bytes = format_bytes(held.bytes)
+        );
+        for file in &held.files {
+            say!("  {}", describe_held_file(file));
+        }
+        say!("Held space per service or process:");
+        say!("  {:>10}  {:>6}  Holder", "Size", "Files");
+        for holder in &held.holders {
+            say!("  {:>10}  {:>6}  {}", format_bytes(holder.bytes), holder.files, holder.holder);
+        }
+    }
+
+    /// Finds the rotated logs below `log_dir`, prints their sizes per service and the files to
+    /// delete and compress according to `policy`, and returns the steps that do so.
+    ///
+    /// Nothing is returned when the open files of some processes cannot be inspected, as any of
+    /// them might still be writing to a rotated log.
+    fn rotated_log_steps(log_dir: &Path, policy: logs::Policy, quarantine: bool) -> Vec<Step> {
+        let rotated = logs::find_rotated(log_dir);
+        if rotated.is_empty() {
+            return Vec::new();
+        }
+
+        say!("Rotated logs below {dir}:", dir = log_dir.display());
+        say!("  {:>10}  {:>6}  Service", "Size", "Files");
+        for service in logs::by_service(&rotated) {
+            say!("  {:>10}  {:>6}  {}", format_bytes(service.bytes), service.files, service.service);
+        }
+
+        let open_files = OpenFiles::scan(Path::new(PROC_DIR));
+        if open_files.inaccessible > 0 {
+            say!(
+                "Skipping rotated logs: the open files of {count} processes cannot be inspected. Run as root to clean them up.",
+                count = open_files.inaccessible
+            );
+            return Vec::new();
+        }
+
+        let cleanup = logs::select(&rotated, &open_files, policy, SystemTime::now());
+        for holder in &cleanup.held_open {
+            say!(
+                "Skipping {path}: held open by process {pid} as file descriptor {fd}",
+                path = holder.target.display(),
+                pid = holder.pid,
+                fd = holder.fd
+            );
+        }
+
+        let list = |heading: &str, paths: &[PathBuf]| {
+            if !paths.is_empty() {
+                say!("{heading}");
+                for path in paths {
+                    say!("  {path}", path = path.display());
+                }
+            }
+        };
+        list("These rotated logs will be deleted:", &cleanup.delete);
+        list("These rotated logs will be compressed:", &cleanup.compress);
+
+        let step = |command: &[&str], paths: &[PathBuf]| {
+            let paths = paths.iter().map(|path| path.to_string_lossy().into_owned());
+            Step::sudo(&command.iter().map(|arg| arg.to_string()).chain(paths).collect::<Vec<_>>())
+        };
+        let mut steps = Vec::new();
+        if !cleanup.delete.is_empty() && quarantine {
+            let quarantine = quarantine::step(&cleanup.delete);
+            steps.push(Step::sudo(&[&[quarantine.program], quarantine.args.as_slice()].concat()));
+        } else if !cleanup.delete.is_empty() {
+            steps.push(step(&["rm", "-f", "--"], &cleanup.delete));
+        }
+        if !cleanup.compress.is_empty() {
+            steps.push(step(&["gzip", "--"], &cleanup.compress));
+        }
+        steps
+    }
+
+    /// Prints the units and boots that wrote the most to the journal.
+    fn print_journal_usage(usage: &journal::JournalUsage) {
+        let rows = |sources: &[journal::Source]| {
+            say!("  {:>10}  {:>10}  Name", "Messages", "Entries");
+            for source in sources {
+                say!(
+                    "  {bytes:>10}  {entries:>10}  {name}",
+                    bytes = format_bytes(source.bytes),
+                    entries = source.entries,
+                    name = source.name
+                );
+            }
+        };
+
+        say!(
+            "Read {entries} journal entries with {bytes} of messages",
+            entries = usage.entries,
+            bytes = format_bytes(usage.bytes)
+        );
+        say!("\nUnits writing the most:");
+        rows(&usage.units);
+        say!("\nBoots writing the most:");
+        rows(&usage.boots);
+    }
+
+    /// Prints the result of a disk usage scan.
+    fn print_analysis(analysis: &Analysis) {
+        let row = |entry: &analyze::Entry| {
+            say!(
+                "  {allocated:>10}  {apparent:>10}  {path}",
+                allocated = format_bytes(entry.size.allocated),
+                apparent = format_bytes(entry.size.apparent),
+                path = entry.path.display()
+            );
+        };
+
+        say!(
+            "Scanned {path}: {allocated} allocated ({apparent} apparent) in {dirs} directories and {files} files",
+            path = analysis.root.path.display(),
+            allocated = format_bytes(analysis.root.size.allocated),
+            apparent = format_bytes(analysis.root.size.apparent),
+            dirs = analysis.directory_count,
+            files = analysis.file_count,
+        );
+        if analysis.errors > 0 {
+            say!("Skipped {errors} entries that could not be read", errors = analysis.errors);
+        }
+
+        say!("\nHeaviest directories:");
+        say!("  {:>10}  {:>10}  Path", "Allocated", "Apparent");
+        analysis.directories.iter().for_each(row);
+
+        say!("\nHeaviest files:");
+        say!("  {:>10}  {:>10}  Path", "Allocated", "Apparent");
+        analysis.files.iter().for_each(row);
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////////////////////////
+// REGION_END: mod cli
+//////////////////////////////////////////////////////////////////////////////////////////////////
@@ -0,0 +1,247 @@
+//! Rotated log files.
+//!
+//! Besides the journal, many services still write text logs below `/var/log`, which `logrotate`
+//! renames to `messages.1`, `syslog.2.gz`, `secure-20230501` or `Xorg.0.log.old` and compresses.
+//! Rotated logs are never written again, so they can be deleted once they are old enough, or
+//! compressed in the meantime. Files that a running process still holds open are never touched.
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use super::procfs::{OpenFile, OpenFiles};
+
+/// The directory holding the text logs.
+pub const LOG_DIR: &str = "/var/log";
+
+/// Rotated logs older than this many days are deleted unless configured otherwise.
+pub const DEFAULT_MAX_AGE_DAYS: u64 = 30;
+
+/// The extensions of compressed logs.
+const COMPRESSED_EXTENSIONS: [&str; 5] = ["gz", "xz", "bz2", "zst", "lz4"];
+
+/// A rotated log file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotatedLog {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The service that wrote the log, e.g. `nginx` for `/var/log/nginx/access.log.1`.
+    pub service: String,
+    /// The size of the file, in bytes.
+    pub bytes: u64,
+    /// The time of the last modification.
+    pub modified: SystemTime,
+    /// Whether the file is already compressed.
+    pub compressed: bool,
+}
+
+/// How rotated logs are cleaned up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    /// Delete rotated logs older than this.
+    pub max_age: Duration,
+    /// Delete the oldest rotated logs until the remaining ones take up at most this many bytes.
+    pub max_size: Option<u64>,
+    /// Compress the remaining rotated logs with `gzip`.
+    pub compress: bool,
+}
+
+/// The files a cleanup deletes and compresses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cleanup {
+    /// The rotated logs to delete.
+    pub delete: Vec<PathBuf>,
+    /// The uncompressed rotated logs to compress.
+    pub compress: Vec<PathBuf>,
+    /// The rotated logs left alone because a process holds them open.
+    pub held_open: Vec<OpenFile>,
+}
+
+/// The rotated logs written by a single service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceLogs {
+    /// The name of the service.
+    pub service: String,
+    /// The number of rotated logs.
+    pub files: u64,
+    /// The total size of the rotated logs, in bytes.
+    pub bytes: u64,
+}
+
+/// Returns `true` if `name` is the file name of a rotated log, such as `messages.1`,
+/// `syslog.2.gz`, `secure-20230501` or `Xorg.0.log.old`.
+pub fn is_rotated(name: &str) -> bool {
+    // Compressed logs are always rotated ones.
+    if strip_compression(name) != name {
+        return true;
+    }
+    if let Some(stem) = name.strip_suffix(".old") {
+        return !stem.is_empty();
+    }
+    match name.rsplit_once(['.', '-']) {
+        Some((stem, suffix))
+            if !stem.is_empty() && !suffix.is_empty() && suffix.bytes().all(|c| c.is_ascii_digit()) =>
+        {
+            // `messages.1`, or `messages-20230501` as named by the `dateext` option of logrotate.
+            name[stem.len()..].starts_with('.') || suffix.len() == 8
+        }
+        _ => false,
+    }
+}
+
+/// Returns `name` without a trailing compression extension.
+fn strip_compression(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if COMPRESSED_EXTENSIONS.contains(&extension) => stem,
+        _ => name,
+    }
+}
+
+/// Returns the service that wrote a rotated log below `log_dir`: the first directory below it, or
+/// the file name without its rotation suffixes for logs directly in `log_dir`.
+fn service(log_dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(log_dir).unwrap_or(path);
+    let mut components = relative.components();
+    let first = components.next().map(|component| component.as_os_str().to_string_lossy().into_owned());
+    if components.next().is_some() {
+        return first.unwrap_or_default();
+    }
+
+    let mut name = strip_compression(&first.unwrap_or_default()).to_string();
+    while is_rotated(&name) {
+        match name.rsplit_once(['.', '-']) {
+            Some((stem, _)) => name = stem.to_string(),
+            None => break,
+        }
+    }
+    name.trim_end_matches(".log").to_string()
+}
+
+/// Finds every rotated log below `log_dir`. Unreadable directories are skipped.
+pub fn find_rotated(log_dir: &Path) -> Vec<RotatedLog> {
+    let mut logs = Vec::new();
+    let mut pending = vec![log_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !metadata.is_file() || !is_rotated(&name) {
+                continue;
+            }
+            logs.push(RotatedLog {
+                service: service(log_dir, &path),
+                bytes: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                compressed: strip_compression(&name) != name,
+                path,
+            });
+        }
+    }
+    logs.sort_by(|a, b| a.path.cmp(&b.path));
+    logs
+}
+
+/// Sums the rotated logs per service, heaviest first.
+pub fn by_service(logs: &[RotatedLog]) -> Vec<ServiceLogs> {
+    let mut services: BTreeMap<&str, ServiceLogs> = BTreeMap::new();
+    for log in logs {
+        let service = services.entry(&log.service).or_insert_with(|| ServiceLogs {
+            service: log.service.clone(),
+            files: 0,
+            bytes: 0,
+        });
+        service.files += 1;
+        service.bytes += log.bytes;
+    }
+    let mut services: Vec<ServiceLogs> = services.into_values().collect();
+    services.sort_by_key(|service| Reverse(service.bytes));
+    services
+}
+
+/// Decides which rotated logs to delete and compress at time `now`.
+///
+/// Logs held open by a process in `open_files` are left alone.
+pub fn select(logs: &[RotatedLog], open_files: &OpenFiles, policy: Policy, now: SystemTime) -> Cleanup {
+    let mut held_open = Vec::new();
+    let mut candidates: Vec<&RotatedLog> = logs
+        .iter()
+        .filter(|log| match open_files.holder(&log.path) {
+            Some(holder) => {
+                held_open.push(holder.clone());
+                false
+            }
+            None => true,
+        })
+        .collect();
+    // Oldest first, so that the size limit removes the oldest logs.
+    candidates.sort_by_key(|log| log.modified);
+
+    let age = |log: &RotatedLog| now.duration_since(log.modified).unwrap_or_default();
+    let (mut delete, mut keep): (Vec<&RotatedLog>, Vec<&RotatedLog>) =
+        candidates.into_iter().partition(|log| age(log) > policy.max_age);
+
+    if let Some(max_size) = policy.max_size {
+        let mut total: u64 = keep.iter().map(|log| log.bytes).sum();
+        let mut over = 0;
+        while total > max_size && over < keep.len() {
+            total -= keep[over].bytes;
+            over += 1;
+        }
+        delete.extend(keep.drain(..over));
+    }
+
+    Cleanup {
+        delete: delete.into_iter().map(|log| log.path.clone()).collect(),
+        compress: if policy.compress {
+            keep.into_iter().filter(|log| !log.compressed).map(|log| log.path.clone()).collect()
+        } else {
+            Vec::new()
+        },
+        held_open,
+    }
+}
+
+/// Parses a size such as `500M`, `2G` or `10GiB` into bytes. The units are powers of 1024, as in
+/// `journalctl --vacuum-size`.
+///
+/// # Errors
+///
+/// Returns an error if the size is not a number with an optional `K`, `M`, `G` or `T` suffix, or
+/// if it does not fit into 64 bits.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let (number, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => size.split_at(index),
+        None => (size, ""),
+    };
+    let exponent = match unit.trim_end_matches(['B', 'b']).trim_end_matches('i').to_ascii_uppercase().as_str() {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return Err(anyhow!("Invalid size: {size}. Use a number with an optional K, M, G or T suffix.")),
+    };
+    let number: u64 = number.parse().map_err(|_| anyhow!("Invalid size: {size}"))?;
+    1024u64
+        .checked_pow(exponent)
+        .and_then(|multiplier| number.checked_mul(multiplier))
+        .ok_or_else(|| anyhow!("Invalid size: {size}. It is too large."))
+}
@@ -0,0 +1,73 @@
+//! Output formats and reporters.
+//!
+//! The library prints nothing itself. The human readable messages of its commands, such as the
+//! plans they run or the caches they found, go through `say!` to the `Reporter` installed with
+//! `set_reporter`, and are discarded when there is none. Programs using the library can collect
+//! them or leave them out and work with the returned results alone. The CLI installs a `Console`:
+//! in the default text format everything is printed to stdout, as it always was, and with
+//! `--format json` stdout is reserved for the final result document, so the messages move to
+//! stderr.
+//!
+//! Prompts are messages too, so interactive sessions need a reporter for the user to see them.
+
+use std::{fmt, sync::RwLock};
+
+use clap::ValueEnum;
+
+/// The reporter receiving the messages of `say!`, if any.
+static REPORTER: RwLock<Option<Box<dyn Reporter>>> = RwLock::new(None);
+
+/// The output formats supported by the CLI.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable text on stdout.
+    #[default]
+    Text,
+    /// A single JSON result document on stdout, with human readable output on stderr.
+    Json,
+}
+
+/// Receives the human readable messages of the library.
+pub trait Reporter: Send + Sync {
+    /// Receives a single message, which may span several lines.
+    fn say(&self, message: fmt::Arguments<'_>);
+}
+
+/// Prints messages to stdout or, in the JSON format, to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Console {
+    /// The format of the run, which decides where messages go.
+    pub format: Format,
+}
+
+impl Reporter for Console {
+    fn say(&self, message: fmt::Arguments<'_>) {
+        match self.format {
+            Format::Text => println!("{message}"),
+            Format::Json => eprintln!("{message}"),
+        }
+    }
+}
+
+/// Sends the messages of `say!` to `reporter` for the rest of the run, or discards them with
+/// `None`, which is the default.
+pub fn set_reporter(reporter: Option<Box<dyn Reporter>>) {
+    *REPORTER.write().unwrap_or_else(|err| err.into_inner()) = reporter;
+}
+
+/// Passes `message` to the installed reporter, if there is one.
+pub fn report(message: fmt::Arguments<'_>) {
+    if let Some(reporter) = REPORTER.read().unwrap_or_else(|err| err.into_inner()).as_ref() {
+        reporter.say(message);
+    }
+}
+
+/// Reports a human readable message, formatted like `println!`, to the reporter installed with
+/// `set_reporter`. Nothing is output without one.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::output::report(format_args!($($arg)*))
+    };
+}
+pub use crate::say;
@@ -0,0 +1,145 @@
+//! Execution plans.
+//!
+//! Every command first describes the external commands it wants to run as a `Plan`, which is then
+//! either executed or, in dry-run mode, printed together with the output of read-only preview
+//! commands such as `dnf autoremove --assumeno` or `journalctl --disk-usage`.
+
+use std::{fmt, path::PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::{execute_cmd, output::say, runner::CommandRunner};
+
+/// A single external command that a task intends to run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Step {
+    /// The program to run, e.g. `sudo`.
+    pub program: String,
+    /// The arguments passed to `program`.
+    pub args: Vec<String>,
+    /// A read-only command that shows what `program` would change. It is only run in dry-run mode.
+    pub preview: Option<Vec<String>>,
+}
+
+impl Step {
+    /// Creates a new step that runs `program` with `args`.
+    pub fn new<S: AsRef<str>>(program: &str, args: &[S]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.as_ref().to_string()).collect(),
+            preview: None,
+        }
+    }
+
+    /// Creates a new step that runs `args` with `sudo`.
+    pub fn sudo<S: AsRef<str>>(args: &[S]) -> Self {
+        Self::new("sudo", args)
+    }
+
+    /// Creates a new step that removes `paths` and everything below them.
+    pub fn remove(paths: &[PathBuf]) -> Self {
+        let mut args = vec![String::from("-rf"), String::from("--")];
+        args.extend(paths.iter().map(|path| path.to_string_lossy().into_owned()));
+        Self::new("rm", &args)
+    }
+
+    /// Sets the read-only command used to preview the effect of this step.
+    pub fn with_preview<S: AsRef<str>>(mut self, preview: &[S]) -> Self {
+        self.preview = Some(preview.iter().map(|arg| arg.as_ref().to_string()).collect());
+        self
+    }
+
+    /// Runs the step, returning its trimmed standard output.
+    pub fn execute(&self, runner: &dyn CommandRunner) -> Result<String> {
+        let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+        execute_cmd(runner, &self.program, &args)
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The ordered list of steps a task will run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Plan {
+    /// A short human readable name of the task, e.g. "Clean package cache".
+    pub task: String,
+    /// The steps, in execution order.
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    /// Creates an empty plan for the given task.
+    pub fn new(task: &str) -> Self {
+        Self { task: task.to_string(), steps: Vec::new() }
+    }
+
+    /// Appends a step to the plan.
+    pub fn with_step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Appends a step to the plan if there is one.
+    pub fn with_optional_step(self, step: Option<Step>) -> Self {
+        match step {
+            Some(step) => self.with_step(step),
+            None => self,
+        }
+    }
+
+    /// Runs every step of the plan in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first step that fails. The remaining steps are not run.
+    pub fn execute(&self, runner: &dyn CommandRunner) -> Result<()> {
+        if self.steps.is_empty() {
+            say!("{task}: nothing to do", task = self.task);
+        }
+        for step in &self.steps {
+            step.execute(runner)?;
+        }
+        Ok(())
+    }
+
+    /// Prints every command line of the plan, followed by the output of its preview command.
+    pub fn print(&self, runner: &dyn CommandRunner) {
+        say!("Dry run: {task}", task = self.task);
+        if self.steps.is_empty() {
+            say!("  nothing to do");
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            say!("  {n}. {step}", n = i + 1);
+            if let Some(preview) = &step.preview {
+                say!("     Preview: {cmd}", cmd = preview.join(" "));
+                for line in run_preview(runner, preview).lines() {
+                    say!("       {line}");
+                }
+            }
+        }
+    }
+}
+
+/// Runs a read-only preview command and returns everything it printed.
+///
+/// Preview commands such as `dnf remove --assumeno` exit with a non-zero status after printing
+/// the transaction, so both output streams are returned regardless of the exit status.
+fn run_preview(runner: &dyn CommandRunner, preview: &[String]) -> String {
+    let Some((program, args)) = preview.split_first() else {
+        return String::new();
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match runner.run(program, &args, None) {
+        Ok(output) => format!("{}\n{}", output.stdout.trim(), output.stderr.trim()).trim().to_string(),
+        Err(err) => format!("Failed to run preview: {err}"),
+    }
+}
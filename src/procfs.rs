@@ -0,0 +1,88 @@
+//! Open file descriptors of running processes.
+//!
+//! Every entry in `/proc/<pid>/fd` is a symbolic link to the file the descriptor refers to. When
+//! that file has been deleted, the link target ends with ` (deleted)`, but the space stays in use
+//! until the descriptor is closed. Descriptors of other users' processes can only be read as root.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The mount point of the proc filesystem.
+pub const PROC_DIR: &str = "/proc";
+
+/// The suffix the kernel appends to the link target of a descriptor whose file was deleted.
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// A file descriptor held by a running process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenFile {
+    /// The ID of the process holding the descriptor.
+    pub pid: u32,
+    /// The descriptor number.
+    pub fd: u32,
+    /// The path of the file, without the ` (deleted)` suffix.
+    pub target: PathBuf,
+    /// Whether the file has been deleted while open.
+    pub deleted: bool,
+}
+
+/// The open files of every process that could be inspected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpenFiles {
+    /// The descriptors that refer to regular paths, skipping sockets, pipes and the like.
+    pub files: Vec<OpenFile>,
+    /// The number of processes whose descriptors could not be read, usually for lack of
+    /// permissions.
+    pub inaccessible: usize,
+}
+
+impl OpenFiles {
+    /// Reads the descriptors of every process below `proc_dir`.
+    pub fn scan(proc_dir: &Path) -> Self {
+        let mut open_files = Self::default();
+        let Ok(processes) = fs::read_dir(proc_dir) else {
+            return open_files;
+        };
+
+        for process in processes.flatten() {
+            let Some(pid) = process.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+                continue;
+            };
+            // Processes may exit while they are being scanned, which is not worth reporting.
+            let descriptors = match fs::read_dir(process.path().join("fd")) {
+                Ok(descriptors) => descriptors,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(_) => {
+                    open_files.inaccessible += 1;
+                    continue;
+                }
+            };
+
+            for descriptor in descriptors.flatten() {
+                let Some(fd) = descriptor.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+                    continue;
+                };
+                let Ok(target) = fs::read_link(descriptor.path()) else {
+                    continue;
+                };
+                // Sockets, pipes and anonymous inodes link to targets such as `socket:[1234]`.
+                let Some(target) = target.to_str().filter(|target| target.starts_with('/')) else {
+                    continue;
+                };
+                let (target, deleted) = match target.strip_suffix(DELETED_SUFFIX) {
+                    Some(target) => (target, true),
+                    None => (target, false),
+                };
+                open_files.files.push(OpenFile { pid, fd, target: PathBuf::from(target), deleted });
+            }
+        }
+        open_files
+    }
+
+    /// Returns a descriptor that holds `path` open, if any process does.
+    pub fn holder(&self, path: &Path) -> Option<&OpenFile> {
+        self.files.iter().find(|file| !file.deleted && file.target == path)
+    }
+}
@@ -0,0 +1,263 @@
+//! The quarantine area.
+//!
+//! In quarantine mode, file-based cleanups move files into a quarantine directory instead of
+//! deleting them, so that they can be restored until the quarantine is purged. Items are renamed,
+//! never copied, so the quarantine directory must be on the same filesystem as the item: files on
+//! the filesystem of the user's data directory go to
+//! `$XDG_DATA_HOME/disk-space-optimizer/quarantine`, all others to a
+//! `.disk-space-optimizer-quarantine` directory at the root of their filesystem.
+//!
+//! Each item gets its own directory holding the item itself as `data` and a `manifest.json` with
+//! its original path, owner, mode, timestamps and size.
+
+use std::{
+    env,
+    ffi::CString,
+    fs, io,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{lchown, MetadataExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    analyze::{self, ScanOptions},
+    plan::Step,
+};
+
+/// The name of the quarantine directory at the root of a filesystem.
+const MOUNT_QUARANTINE_DIR: &str = ".disk-space-optimizer-quarantine";
+
+/// The file listing the mount points of the current process.
+const MOUNTS: &str = "/proc/self/mounts";
+
+/// The name of the quarantined item inside its entry directory.
+const DATA: &str = "data";
+
+/// The name of the manifest inside an entry directory.
+const MANIFEST: &str = "manifest.json";
+
+/// The metadata of a quarantined item, as it was before it was quarantined.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The path the item was moved from.
+    pub original_path: PathBuf,
+    /// The user ID of the owner.
+    pub uid: u32,
+    /// The group ID of the owner.
+    pub gid: u32,
+    /// The permission bits.
+    pub mode: u32,
+    /// The time of the last modification.
+    pub modified: SystemTime,
+    /// The time of the last access.
+    pub accessed: SystemTime,
+    /// The time the item was quarantined.
+    pub quarantined: SystemTime,
+    /// The space allocated to the item and everything below it, in bytes.
+    pub bytes: u64,
+}
+
+/// A quarantined item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    /// The ID of the entry, unique within its quarantine directory.
+    pub id: String,
+    /// The entry directory holding the item and its manifest.
+    pub dir: PathBuf,
+    /// The metadata of the item.
+    pub manifest: Manifest,
+}
+
+/// Returns the step that moves `paths` into the quarantine by running this program's `quarantine`
+/// command.
+pub fn step(paths: &[PathBuf]) -> Step {
+    let mut args = vec![String::from("quarantine"), String::from("--")];
+    args.extend(paths.iter().map(|path| path.to_string_lossy().into_owned()));
+    Step::new(&program(), &args)
+}
+
+/// Returns the path of the running program, so that steps run the same version of it.
+pub fn program() -> String {
+    env::current_exe().map_or_else(|_| String::from("disk-space-optimizer"), |exe| exe.to_string_lossy().into_owned())
+}
+
+/// Returns the quarantine directory in the user's data directory, if the home directory is known.
+pub fn user_dir() -> Option<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_home.join("disk-space-optimizer/quarantine"))
+}
+
+/// Returns every existing quarantine directory: the one in the user's data directory and those at
+/// the root of each mounted filesystem.
+pub fn dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = user_dir().into_iter().collect();
+    let mounts = fs::read_to_string(MOUNTS).unwrap_or_default();
+    for mount_point in mounts.lines().filter_map(|line| line.split(' ').nth(1)) {
+        // Spaces and other special characters in mount points are escaped as octal numbers.
+        let mount_point = mount_point.replace("\\040", " ").replace("\\011", "\t").replace("\\134", "\\");
+        dirs.push(Path::new(&mount_point).join(MOUNT_QUARANTINE_DIR));
+    }
+    dirs.retain(|dir| dir.is_dir());
+    dirs.dedup();
+    dirs
+}
+
+/// Returns the quarantine directory for `path`: `user_dir` if it is on the same filesystem, or
+/// the quarantine directory at the root of the filesystem of `path`.
+pub fn dir_for(path: &Path, user_dir: Option<&Path>) -> Result<PathBuf> {
+    let device = fs::symlink_metadata(path).with_context(|| format!("Failed to read {}", path.display()))?.dev();
+    if let Some(user_dir) = user_dir {
+        // The quarantine directory itself may not exist yet, so look at its closest existing parent.
+        let existing = user_dir.ancestors().find(|dir| dir.exists());
+        if existing.and_then(|dir| fs::metadata(dir).ok()).is_some_and(|metadata| metadata.dev() == device) {
+            return Ok(user_dir.to_path_buf());
+        }
+    }
+
+    let mut root = path.parent().unwrap_or(path);
+    while let Some(parent) = root.parent() {
+        if fs::metadata(parent).map_or(true, |metadata| metadata.dev() != device) {
+            break;
+        }
+        root = parent;
+    }
+    Ok(root.join(MOUNT_QUARANTINE_DIR))
+}
+
+/// Moves `path` into the quarantine directory `dir` at time `now`.
+///
+/// # Errors
+///
+/// Returns an error if `path` cannot be read, or if it cannot be renamed into `dir`, e.g. because
+/// `dir` is on another filesystem.
+pub fn quarantine(path: &Path, dir: &Path, now: SystemTime) -> Result<Entry> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // Symbolic links are quarantined themselves, so the path is made absolute without resolving them.
+    let path = std::path::absolute(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let metadata = fs::symlink_metadata(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let options = ScanOptions { one_file_system: true, top: 0 };
+    let manifest = Manifest {
+        original_path: path.clone(),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        mode: metadata.mode(),
+        modified: metadata.modified()?,
+        accessed: metadata.accessed()?,
+        quarantined: now,
+        bytes: analyze::scan(&path, options).map_or(0, |analysis| analysis.root.size.allocated),
+    };
+
+    let secs = now.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    let id = format!("{secs}-{pid}-{n}", pid = process::id(), n = COUNTER.fetch_add(1, Ordering::Relaxed));
+    let entry_dir = dir.join(&id);
+    fs::create_dir_all(&entry_dir).with_context(|| format!("Failed to create {}", entry_dir.display()))?;
+    // Other users must not be able to look into the quarantine of system files.
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    fs::write(entry_dir.join(MANIFEST), serde_json::to_string_pretty(&manifest)?)?;
+    if let Err(err) = fs::rename(&path, entry_dir.join(DATA)) {
+        fs::remove_dir_all(&entry_dir)?;
+        return Err(err).with_context(|| format!("Failed to move {} to {}", path.display(), dir.display()));
+    }
+    Ok(Entry { id, dir: entry_dir, manifest })
+}
+
+/// Lists the entries of the quarantine directories `dirs`, oldest first. Entries without a
+/// readable manifest are skipped.
+pub fn list(dirs: &[PathBuf]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for dir in dirs {
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let manifest = fs::read_to_string(entry.path().join(MANIFEST)).ok();
+            let Some(manifest) = manifest.and_then(|manifest| serde_json::from_str(&manifest).ok()) else {
+                continue;
+            };
+            entries.push(Entry { id: entry.file_name().to_string_lossy().into_owned(), dir: entry.path(), manifest });
+        }
+    }
+    entries.sort_by(|a, b| a.manifest.quarantined.cmp(&b.manifest.quarantined).then_with(|| a.id.cmp(&b.id)));
+    entries
+}
+
+/// Moves the item of `entry` back to its original path and restores its owner, mode and
+/// timestamps, then removes the entry.
+///
+/// # Errors
+///
+/// Returns an error if something else now exists at the original path, or if the item cannot be
+/// moved back.
+pub fn restore(entry: &Entry) -> Result<()> {
+    let manifest = &entry.manifest;
+    let original = &manifest.original_path;
+    if fs::symlink_metadata(original).is_ok() {
+        return Err(anyhow!("Cannot restore {}: the path exists.", original.display()));
+    }
+    if let Some(parent) = original.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    // The metadata is restored while the item is still in quarantine, so that a failure leaves the
+    // entry complete and the restore can be retried.
+    let data = entry.dir.join(DATA);
+    let metadata = fs::symlink_metadata(&data)?;
+    if (metadata.uid(), metadata.gid()) != (manifest.uid, manifest.gid) {
+        lchown(&data, Some(manifest.uid), Some(manifest.gid))?;
+    }
+    if !metadata.file_type().is_symlink() {
+        fs::set_permissions(&data, fs::Permissions::from_mode(manifest.mode))?;
+    }
+    set_times(&data, manifest.accessed, manifest.modified)
+        .with_context(|| format!("Failed to set the timestamps of {}", data.display()))?;
+    fs::rename(&data, original)
+        .with_context(|| format!("Failed to move {} back to {}", entry.id, original.display()))?;
+    fs::remove_dir_all(&entry.dir).with_context(|| format!("Failed to remove {}", entry.dir.display()))
+}
+
+/// Sets the access and modification times of `path`, or of the link itself if it is a symbolic
+/// link, which `File::set_times` cannot do.
+fn set_times(path: &Path, accessed: SystemTime, modified: SystemTime) -> io::Result<()> {
+    let timespec = |time: SystemTime| {
+        let since_epoch = time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        libc::timespec {
+            tv_sec: since_epoch.as_secs() as libc::time_t,
+            tv_nsec: since_epoch.subsec_nanos() as libc::c_long,
+        }
+    };
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let times = [timespec(accessed), timespec(modified)];
+
+    // SAFETY: `c_path` is a valid NUL-terminated string and `times` holds the two timestamps
+    // `utimensat` reads.
+    if unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Deletes `entry` for good, freeing its space.
+///
+/// # Errors
+///
+/// Returns an error if the entry cannot be removed.
+pub fn purge(entry: &Entry) -> Result<()> {
+    fs::remove_dir_all(&entry.dir).with_context(|| format!("Failed to remove {}", entry.dir.display()))
+}
+
+/// Returns the entries quarantined more than `older_than` before `now`.
+pub fn expired(entries: &[Entry], older_than: Duration, now: SystemTime) -> Vec<&Entry> {
+    entries
+        .iter()
+        .filter(|entry| now.duration_since(entry.manifest.quarantined).unwrap_or_default() > older_than)
+        .collect()
+}
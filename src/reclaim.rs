@@ -0,0 +1,67 @@
+//! Free-space targets.
+//!
+//! `reclaim --target 10GiB` frees a given amount of space on one filesystem while taking as little
+//! risk as possible. Every task that can run without a selection and estimate what it would reclaim
+//! there is a candidate. The
+//! candidates are ranked by risk, lowest first, and among equal risks by their estimate, largest
+//! first. The plan is the shortest prefix of that ranking whose estimates add up to the target.
+//!
+//! Estimates are only estimates, so the plan runs task by task while the free space of the
+//! filesystem is measured, and stops as soon as it has grown by the target.
+
+use std::{cmp::Reverse, path::PathBuf};
+
+use serde::Serialize;
+
+use super::task::Risk;
+
+/// A task that can take part in a plan, with the space it is estimated to reclaim.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Candidate {
+    /// The ID of the task, e.g. `clean-package-cache`.
+    pub id: &'static str,
+    /// The human readable title of the task.
+    pub title: &'static str,
+    /// How much harm the task can do.
+    pub risk: Risk,
+    /// The estimated space the task would reclaim, in bytes.
+    pub estimate: u64,
+}
+
+/// Returns the candidates to run to reclaim `target` bytes, in the order to run them: ranked by
+/// risk and then by estimate, and cut off once their estimates add up to `target`. When all of them
+/// together fall short of the target, all of them are returned. Candidates that would reclaim
+/// nothing are left out.
+pub fn plan(mut candidates: Vec<Candidate>, target: u64) -> Vec<Candidate> {
+    candidates.retain(|candidate| candidate.estimate > 0);
+    candidates.sort_by_key(|candidate| (candidate.risk, Reverse(candidate.estimate)));
+
+    let mut planned = 0;
+    candidates
+        .into_iter()
+        .take_while(|candidate| {
+            let needed = planned < target;
+            planned += candidate.estimate;
+            needed
+        })
+        .collect()
+}
+
+/// The outcome of a `reclaim` run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Outcome {
+    /// The path whose filesystem space was reclaimed on.
+    pub mount: PathBuf,
+    /// The space to reclaim, in bytes.
+    pub target: u64,
+    /// The planned tasks, in the order they were to run.
+    pub plan: Vec<Candidate>,
+    /// The IDs of the tasks that ran. Tasks after the target was reached are left out.
+    pub ran: Vec<&'static str>,
+    /// The free space of the filesystem before the first task, in bytes.
+    pub free_before: u64,
+    /// The free space of the filesystem after the last task, in bytes.
+    pub free_after: u64,
+    /// Whether the free space grew by at least the target.
+    pub reached: bool,
+}
@@ -0,0 +1,123 @@
+//! Run reports.
+//!
+//! A `Report` collects the outcome of every task run during an invocation: the commands it ran
+//! with their exit codes and output, the space it reclaimed and any errors. It is printed as a
+//! short summary in text mode, or as the JSON result document with `--format json`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::{
+    output::{say, Format},
+    runner::CommandRecord,
+    space::{format_signed_bytes, Snapshot, WATCHED_PATHS},
+};
+
+/// The outcome of a single task.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskReport {
+    /// The human readable name of the task.
+    pub task: String,
+    /// Every external command the task ran, in order.
+    pub commands: Vec<CommandRecord>,
+    /// The bytes freed on the measured filesystems, or `None` if space was not measured, e.g. in
+    /// dry-run mode.
+    pub bytes_reclaimed: Option<i64>,
+    /// The structured result of the task, such as a disk usage analysis or a dry-run plan.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    /// The errors the task failed with.
+    pub errors: Vec<String>,
+}
+
+/// The outcome of every task run during an invocation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    /// Whether the tasks only printed their plan.
+    pub dry_run: bool,
+    /// The tasks, in execution order.
+    pub tasks: Vec<TaskReport>,
+}
+
+impl Report {
+    /// Creates an empty report.
+    pub fn new(dry_run: bool) -> Self {
+        Self { dry_run, tasks: Vec::new() }
+    }
+
+    /// Runs `task` and records its outcome.
+    ///
+    /// When `measure` is given, the free space of the watched filesystems and of the filesystems
+    /// holding the given paths is sampled before and after the task. Space is recorded even if the
+    /// task fails, since it may have removed files before failing. `commands` is called afterwards
+    /// to collect the commands the task ran.
+    pub fn run<F, C>(&mut self, name: &str, measure: Option<&[PathBuf]>, task: F, commands: C) -> Result<()>
+    where
+        F: FnOnce() -> Result<Option<serde_json::Value>>,
+        C: FnOnce() -> Vec<CommandRecord>,
+    {
+        let paths: Option<Vec<&Path>> = measure
+            .map(|paths| WATCHED_PATHS.iter().map(Path::new).chain(paths.iter().map(PathBuf::as_path)).collect());
+        let before = paths.as_deref().map(Snapshot::take);
+        let result = task();
+        let bytes_reclaimed = paths.zip(before).map(|(paths, before)| Snapshot::take(&paths).reclaimed_since(&before));
+
+        let mut report =
+            TaskReport { task: name.to_string(), commands: commands(), bytes_reclaimed, ..Default::default() };
+        let result = match result {
+            Ok(details) => {
+                report.details = details;
+                Ok(())
+            }
+            Err(err) => {
+                report.errors.push(format!("{err:#}"));
+                Err(err)
+            }
+        };
+        self.tasks.push(report);
+        result
+    }
+
+    /// Returns the total number of bytes reclaimed by all tasks.
+    pub fn total(&self) -> i64 {
+        self.tasks.iter().filter_map(|task| task.bytes_reclaimed).sum()
+    }
+
+    /// Returns the report as the JSON result document printed with `--format json`.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "dry_run": self.dry_run,
+            "tasks": self.tasks,
+            "bytes_reclaimed": self.total(),
+        })
+    }
+
+    /// Prints the report in the given format.
+    ///
+    /// In text mode, the space reclaimed by each measured task is listed, followed by the total.
+    /// In JSON mode, the whole report is printed to stdout as a single document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the report cannot be serialized.
+    pub fn print(&self, format: Format) -> Result<()> {
+        match format {
+            Format::Json => println!("{}", serde_json::to_string_pretty(&self.to_json())?),
+            Format::Text => {
+                let measured: Vec<_> = self.tasks.iter().filter(|task| task.bytes_reclaimed.is_some()).collect();
+                if measured.is_empty() {
+                    return Ok(());
+                }
+                say!("Reclaimed space:");
+                for task in measured {
+                    let bytes = format_signed_bytes(task.bytes_reclaimed.unwrap_or_default());
+                    say!("  {task}: {bytes}", task = task.task);
+                }
+                say!("  Total: {total}", total = format_signed_bytes(self.total()));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+//! Running external commands.
+//!
+//! Every subprocess the disk space optimizer spawns goes through the `CommandRunner` trait. The
+//! `SystemRunner` runs commands for real, while tests use the scripted `FakeRunner`, which answers
+//! each expected argument list with canned output instead of touching the system.
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+/// The captured result of a finished command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CmdOutput {
+    /// The exit code, or `None` if the process was terminated by a signal.
+    pub status: Option<i32>,
+    /// Everything the command wrote to standard output.
+    pub stdout: String,
+    /// Everything the command wrote to standard error.
+    pub stderr: String,
+}
+
+impl CmdOutput {
+    /// Creates the output of a command that exited with status 0 and printed `stdout`.
+    #[cfg(test)]
+    pub fn ok(stdout: &str) -> Self {
+        Self { status: Some(0), stdout: stdout.to_string(), stderr: String::new() }
+    }
+
+    /// Creates the output of a command that exited with `status` and printed `stderr`.
+    #[cfg(test)]
+    pub fn failed(status: i32, stderr: &str) -> Self {
+        Self { status: Some(status), stdout: String::new(), stderr: stderr.to_string() }
+    }
+
+    /// Returns `true` if the command exited with status 0.
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+
+/// Spawns external commands and captures their output.
+pub trait CommandRunner: Sync {
+    /// Runs `program` with `args`, writing `stdin` to its standard input when given, and waits for
+    /// it to finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command could not be started. A command that starts but exits with
+    /// a non-zero status is not an error; check `CmdOutput::success`.
+    fn run(&self, program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<CmdOutput>;
+}
+
+/// Runs commands on the real system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<CmdOutput> {
+        let mut command = Command::new(program);
+        command.args(args);
+
+        let output = match stdin {
+            Some(input) => {
+                let mut child = command
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("Failed to execute command: {program}"))?;
+                child.stdin.take().ok_or_else(|| anyhow!("Failed to open stdin of {program}"))?.write_all(input)?;
+                child.wait_with_output()?
+            }
+            None => command.output().with_context(|| format!("Failed to execute command: {program}"))?,
+        };
+
+        Ok(CmdOutput {
+            status: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// A command that was run, together with its outcome.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CommandRecord {
+    /// The program followed by its arguments.
+    pub argv: Vec<String>,
+    /// The exit code, or `None` if the process was terminated by a signal or failed to start.
+    pub exit_code: Option<i32>,
+    /// Everything the command wrote to standard output.
+    pub stdout: String,
+    /// Everything the command wrote to standard error, or the reason it failed to start.
+    pub stderr: String,
+}
+
+/// Wraps another runner and records every command it runs, so that reports can list them.
+pub struct RecordingRunner {
+    inner: Box<dyn CommandRunner>,
+    records: Mutex<Vec<CommandRecord>>,
+}
+
+impl RecordingRunner {
+    /// Creates a runner that records the commands run by `inner`.
+    pub fn new(inner: Box<dyn CommandRunner>) -> Self {
+        Self { inner, records: Mutex::new(Vec::new()) }
+    }
+
+    /// Returns the commands recorded so far and clears the record.
+    pub fn take_records(&self) -> Vec<CommandRecord> {
+        std::mem::take(&mut *self.records.lock().unwrap())
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<CmdOutput> {
+        let result = self.inner.run(program, args, stdin);
+        let argv = std::iter::once(program).chain(args.iter().copied()).map(String::from).collect();
+        let record = match &result {
+            Ok(output) => CommandRecord {
+                argv,
+                exit_code: output.status,
+                stdout: output.stdout.clone(),
+                stderr: output.stderr.clone(),
+            },
+            Err(err) => CommandRecord { argv, exit_code: None, stdout: String::new(), stderr: format!("{err:#}") },
+        };
+        self.records.lock().unwrap().push(record);
+        result
+    }
+}
+
+/// A scripted runner for tests.
+///
+/// Each call to `run` must match the next expected argument list, and is answered with its canned
+/// output. Unexpected commands fail the call instead of running anything.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct FakeRunner {
+    script: Mutex<std::collections::VecDeque<(Vec<String>, CmdOutput)>>,
+}
+
+#[cfg(test)]
+impl FakeRunner {
+    /// Creates a runner that expects no commands.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects `argv` (the program followed by its arguments) to run next, answering with `output`.
+    pub fn expect(self, argv: &[&str], output: CmdOutput) -> Self {
+        let argv = argv.iter().map(|arg| arg.to_string()).collect();
+        self.script.lock().unwrap().push_back((argv, output));
+        self
+    }
+
+    /// Returns `true` once every expected command has run.
+    pub fn is_done(&self) -> bool {
+        self.script.lock().unwrap().is_empty()
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for FakeRunner {
+    fn run(&self, program: &str, args: &[&str], _stdin: Option<&[u8]>) -> Result<CmdOutput> {
+        let argv: Vec<String> = std::iter::once(program).chain(args.iter().copied()).map(String::from).collect();
+        let (expected, output) = self
+            .script
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| anyhow!("Unexpected command: {argv}", argv = argv.join(" ")))?;
+
+        if expected != argv {
+            return Err(anyhow!(
+                "Unexpected command: {argv}, expected: {expected}",
+                argv = argv.join(" "),
+                expected = expected.join(" ")
+            ));
+        }
+        Ok(output)
+    }
+}
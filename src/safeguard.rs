@@ -0,0 +1,106 @@
+//! Safeguards against removing the kernels the system boots from.
+//!
+//! The running kernel is identified with `uname -r`, and the default boot entry from the Boot
+//! Loader Specification entries in `/boot/loader/entries`, using the `saved_entry` of the GRUB
+//! environment block when there is one. Both are protected: they are marked in the kernel menu and
+//! dropped from every removal plan, even with `--yes`.
+
+use std::{fs, path::Path};
+
+use anyhow::Result;
+
+use super::{backend::PackageManager, execute_cmd, output::say, runner::CommandRunner, version::rpmvercmp};
+
+/// The directory holding the kernels and the boot loader configuration.
+pub const BOOT_DIR: &str = "/boot";
+
+/// The GRUB environment blocks that may hold `saved_entry`, relative to `BOOT_DIR`.
+const GRUBENV_PATHS: [&str; 2] = ["grub2/grubenv", "grub/grubenv"];
+
+/// The kernel releases that must never be removed, as printed by `uname -r`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProtectedKernels {
+    /// The release of the running kernel.
+    pub running: String,
+    /// The release of the kernel booted by default, if it could be determined.
+    pub default_entry: Option<String>,
+}
+
+impl ProtectedKernels {
+    /// Identifies the running kernel and the default boot entry below `boot_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `uname -r` fails. A missing or unreadable boot loader configuration is
+    /// not an error; only the running kernel is protected then.
+    pub fn detect(runner: &dyn CommandRunner, boot_dir: &Path) -> Result<Self> {
+        let running = execute_cmd(runner, "uname", &["-r"])?;
+        Ok(Self { running, default_entry: default_boot_entry(boot_dir) })
+    }
+
+    /// Returns why `package` is protected, or `None` if it may be removed.
+    pub fn reason(&self, package_manager: &dyn PackageManager, package: &str) -> Option<&'static str> {
+        if package_manager.is_kernel_release(package, &self.running) {
+            Some("running kernel")
+        } else if self
+            .default_entry
+            .as_deref()
+            .is_some_and(|release| package_manager.is_kernel_release(package, release))
+        {
+            Some("default boot entry")
+        } else {
+            None
+        }
+    }
+
+    /// Drops every protected package from `packages`, telling the user about each one.
+    pub fn filter<'a>(&self, package_manager: &dyn PackageManager, packages: Vec<&'a str>) -> Vec<&'a str> {
+        packages
+            .into_iter()
+            .filter(|package| match self.reason(package_manager, package) {
+                Some(reason) => {
+                    say!("Refusing to remove {package}: it is the {reason}.");
+                    false
+                }
+                None => true,
+            })
+            .collect()
+    }
+}
+
+/// Returns the kernel release of the default boot entry, or `None` if there are no BLS entries.
+///
+/// The entry named by `saved_entry` in the GRUB environment block wins. Without one, GRUB boots the
+/// entry with the highest version, as it sorts BLS entries newest first.
+pub fn default_boot_entry(boot_dir: &Path) -> Option<String> {
+    let entries_dir = boot_dir.join("loader/entries");
+    let mut entries: Vec<(String, String)> = fs::read_dir(entries_dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "conf" {
+                return None;
+            }
+            let id = path.file_stem()?.to_str()?.to_string();
+            let contents = fs::read_to_string(&path).ok()?;
+            let version = contents
+                .lines()
+                .find_map(|line| line.split_once(char::is_whitespace).filter(|(key, _)| *key == "version"))?
+                .1
+                .trim()
+                .to_string();
+            Some((id, version))
+        })
+        .collect();
+
+    let saved_entry = GRUBENV_PATHS.iter().find_map(|path| {
+        let contents = fs::read_to_string(boot_dir.join(path)).ok()?;
+        contents.lines().find_map(|line| line.strip_prefix("saved_entry=")).map(str::to_string)
+    });
+    if let Some((_, version)) = saved_entry.and_then(|saved| entries.iter().find(|(id, _)| *id == saved)) {
+        return Some(version.clone());
+    }
+
+    entries.sort_by(|(_, a), (_, b)| rpmvercmp(a, b));
+    entries.pop().map(|(_, version)| version)
+}
@@ -0,0 +1,177 @@
+//! Per-invocation state shared by every command.
+//!
+//! A `Session` is built once from the parsed command-line flags and handed to
+//! `Commands::execute`, so that global options such as `--backend`, `--dry-run` and `--format`
+//! reach every command without growing its argument list.
+//!
+//! The session is also the only place that asks the user anything. In non-interactive mode, or
+//! when stdin is not a terminal, every prompt fails with an error naming the flag or argument that
+//! answers it, so that runs from cron or Ansible never hang waiting for input.
+
+use std::{
+    io::{self, IsTerminal},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use dialoguer::{theme::ColorfulTheme, MultiSelect};
+
+use super::{
+    backend::{Backend, PackageManager},
+    cli::Cli,
+    config::Config,
+    history,
+    output::{say, Format},
+    plan::Step,
+    quarantine, read_line,
+    runner::{CommandRunner, RecordingRunner, SystemRunner},
+};
+
+/// The state shared by all commands of a single run of the CLI.
+pub struct Session {
+    /// Runs every external command, recording it for the report.
+    pub runner: RecordingRunner,
+    /// The package manager used to query and remove packages.
+    pub package_manager: Box<dyn PackageManager>,
+    /// When set, commands print their plan instead of executing it.
+    pub dry_run: bool,
+    /// The format of the final report.
+    pub format: Format,
+    /// When set, confirmations are answered with yes.
+    pub assume_yes: bool,
+    /// When unset, prompts fail instead of waiting for input.
+    pub interactive: bool,
+    /// The defaults from the configuration files, which command-line flags override.
+    pub config: Config,
+    /// When set, file-based cleanups move files into the quarantine instead of deleting them.
+    pub quarantine: bool,
+    /// The ID of this run, under which package removals are recorded in the history.
+    pub run_id: String,
+    /// The directory of the removal history. Removals are not recorded when unset.
+    pub state_dir: Option<PathBuf>,
+    /// Set once the user approved a plan of several tasks, such as that of `reclaim`, so that the
+    /// tasks do not ask again. Confirmations are then answered with yes.
+    pub approved: AtomicBool,
+}
+
+impl Session {
+    /// Creates a new session from the parsed command-line arguments.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no `--backend` was given and the backend cannot be detected, or if a
+    /// configuration file cannot be parsed.
+    pub fn from_cli(cli: &Cli) -> Result<Self> {
+        let backend = match cli.backend {
+            Some(backend) => backend,
+            None => Backend::detect()?,
+        };
+
+        let config = Config::load()?;
+        Ok(Self {
+            quarantine: cli.quarantine || config.quarantine.enabled.unwrap_or(false),
+            dry_run: cli.dry_run,
+            format: cli.format,
+            assume_yes: cli.yes,
+            interactive: !cli.non_interactive && io::stdin().is_terminal(),
+            config,
+            state_dir: history::state_dir(),
+            ..Self::new(Box::new(SystemRunner), backend)
+        })
+    }
+
+    /// Creates a non-interactive session with default options that runs commands through `runner`.
+    pub fn new(runner: Box<dyn CommandRunner>, backend: Backend) -> Self {
+        Self {
+            runner: RecordingRunner::new(runner),
+            package_manager: backend.package_manager(),
+            dry_run: false,
+            format: Format::default(),
+            assume_yes: false,
+            interactive: false,
+            config: Config::default(),
+            quarantine: false,
+            run_id: history::new_run_id(SystemTime::now()),
+            state_dir: None,
+            approved: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the step that deletes `paths`, or moves them into the quarantine in quarantine mode.
+    pub fn remove_step(&self, paths: &[PathBuf]) -> Step {
+        if self.quarantine {
+            quarantine::step(paths)
+        } else {
+            Step::remove(paths)
+        }
+    }
+
+    /// Answers every further confirmation with yes, once the user approved a plan of several tasks.
+    pub fn approve(&self) {
+        self.approved.store(true, Ordering::Relaxed);
+    }
+
+    /// Asks the user to confirm `prompt` with `y` or `n`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the answer is neither `y` nor `n`, or if input would be required in
+    /// non-interactive mode without `--yes`.
+    pub fn confirm(&self, prompt: &str) -> Result<bool> {
+        if self.assume_yes {
+            say!("{prompt} (y/N): y (--yes)");
+            return Ok(true);
+        }
+        if self.approved.load(Ordering::Relaxed) {
+            say!("{prompt} (y/N): y (approved)");
+            return Ok(true);
+        }
+        if !self.interactive {
+            return Err(anyhow!("Confirmation required to {prompt}. Pass --yes to confirm without a prompt."));
+        }
+
+        say!("{prompt} (y/N)");
+        let resp = read_line()?;
+        match resp.trim() {
+            "y" => Ok(true),
+            "n" | "" => Ok(false),
+            _ => Err(anyhow!("Invalid response. Type either 'y' or 'n")),
+        }
+    }
+
+    /// Asks the user to pick any number of `items`, returning the indices of the picked items.
+    /// `defaults` marks the items that are picked initially.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prompt fails, or if input would be required in non-interactive
+    /// mode. `hint` tells the user how to pass the selection on the command line instead.
+    pub fn select<T: ToString>(&self, prompt: &str, items: &[T], defaults: &[bool], hint: &str) -> Result<Vec<usize>> {
+        if !self.interactive {
+            return Err(anyhow!("Selection required: {prompt} {hint}"));
+        }
+        Ok(MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(items)
+            .defaults(defaults)
+            .interact()?)
+    }
+
+    /// Asks the user for a value, returning `default` when the answer is empty or cannot be parsed.
+    ///
+    /// In non-interactive mode `default` is returned without asking.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from stdin fails.
+    pub fn ask<T: std::str::FromStr + std::fmt::Display>(&self, prompt: &str, default: T) -> Result<T> {
+        if !self.interactive {
+            say!("{prompt} {default}");
+            return Ok(default);
+        }
+        say!("{prompt}");
+        Ok(read_line()?.trim().parse::<T>().unwrap_or(default))
+    }
+}
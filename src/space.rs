@@ -0,0 +1,141 @@
+//! Free space accounting.
+//!
+//! Before and after every command, the free space of the filesystems that cleanup tasks touch is
+//! sampled with `statvfs(3)`. The difference is reported per task and in total at the end of a
+//! run by the `report` module, so the user can see how much space was actually reclaimed.
+
+use std::{
+    ffi::CString,
+    io,
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::Path,
+};
+
+/// The paths whose filesystems are sampled before and after each task, besides those of the paths
+/// the task itself names, such as the cache directories it removes.
+pub const WATCHED_PATHS: [&str; 5] = ["/", "/var", "/boot", "/var/log/journal", "/var/cache/dnf"];
+
+/// The size and free space of a single filesystem, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The total size of the filesystem.
+    pub total: u64,
+    /// The free space, including blocks reserved for the superuser.
+    pub free: u64,
+    /// The free space available to unprivileged users.
+    pub available: u64,
+}
+
+/// Queries the usage of the filesystem containing `path` with `statvfs(3)`.
+///
+/// # Errors
+///
+/// Returns an error if the path contains a NUL byte or if `statvfs` fails, e.g. because the path
+/// does not exist.
+pub fn statvfs(path: &Path) -> io::Result<Usage> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: `c_path` is a valid NUL-terminated string and `stat` points to writable memory large
+    // enough for a `statvfs` struct, which is only read after the call reported success.
+    let stat = unsafe {
+        if libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        stat.assume_init()
+    };
+
+    let fragment = stat.f_frsize as u64;
+    Ok(Usage {
+        total: stat.f_blocks as u64 * fragment,
+        free: stat.f_bfree as u64 * fragment,
+        available: stat.f_bavail as u64 * fragment,
+    })
+}
+
+/// Returns the device number of the filesystem containing `path`, looking at its closest existing
+/// ancestor when `path` does not exist yet.
+pub fn device(path: &Path) -> Option<u64> {
+    path.ancestors().find_map(|path| path.metadata().ok()).map(|metadata| metadata.dev())
+}
+
+/// The usage of a filesystem, identified by its device number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filesystem {
+    /// The device number shared by every file on the filesystem.
+    pub device: u64,
+    /// The first sampled path found on this filesystem.
+    pub path: String,
+    /// The sampled usage.
+    pub usage: Usage,
+}
+
+/// The usage of every distinct filesystem behind a set of paths at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The sampled filesystems, without duplicates.
+    pub filesystems: Vec<Filesystem>,
+}
+
+impl Snapshot {
+    /// Samples the filesystems of the given paths. Paths that do not exist are skipped, and paths
+    /// on the same filesystem are only counted once.
+    pub fn take<P: AsRef<Path>>(paths: &[P]) -> Self {
+        let mut filesystems: Vec<Filesystem> = Vec::new();
+        for path in paths.iter().map(AsRef::as_ref) {
+            let Ok(metadata) = path.metadata() else {
+                continue;
+            };
+            if filesystems.iter().any(|fs| fs.device == metadata.dev()) {
+                continue;
+            }
+            if let Ok(usage) = statvfs(path) {
+                filesystems.push(Filesystem { device: metadata.dev(), path: path.display().to_string(), usage });
+            }
+        }
+        Self { filesystems }
+    }
+
+    /// Returns the number of bytes freed on the filesystems of this snapshot since `before`.
+    ///
+    /// The result is negative when the filesystems gained data, e.g. because a package manager
+    /// downloaded metadata while cleaning up.
+    pub fn reclaimed_since(&self, before: &Snapshot) -> i64 {
+        self.filesystems
+            .iter()
+            .filter_map(|after| {
+                let before = before.filesystems.iter().find(|fs| fs.device == after.device)?;
+                Some(after.usage.free as i64 - before.usage.free as i64)
+            })
+            .sum()
+    }
+}
+
+/// Formats a number of bytes using binary units, e.g. `1.5 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {unit}", unit = UNITS[unit])
+    }
+}
+
+/// Formats a signed number of bytes, prefixing space that was consumed rather than freed with `-`.
+pub fn format_signed_bytes(bytes: i64) -> String {
+    let formatted = format_bytes(bytes.unsigned_abs());
+    if bytes < 0 {
+        format!("-{formatted}")
+    } else {
+        formatted
+    }
+}
@@ -0,0 +1,191 @@
+//! Cargo target directories.
+//!
+//! Every Cargo project keeps its build output in a `target/` directory next to `Cargo.toml`, which
+//! easily grows to several gigabytes and is never cleaned up by Cargo itself. Cargo marks these
+//! directories with a `CACHEDIR.TAG` file. Below it, each profile such as `debug` or
+//! `x86_64-unknown-linux-gnu/release` has a `.fingerprint` directory that is updated on every
+//! build, and an `incremental` directory holding the incremental compilation cache, which is
+//! usually the largest part and always safe to remove.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use super::analyze::{self, ScanOptions};
+
+/// The first line of the `CACHEDIR.TAG` file, as defined by the Cache Directory Tagging
+/// Specification.
+const CACHEDIR_TAG_SIGNATURE: &str = "Signature: 8a477f597d28d172789f06886806bc55";
+
+/// A build profile below a target directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Profile {
+    /// The profile directory relative to the target directory, e.g. `debug` or
+    /// `x86_64-unknown-linux-gnu/release`.
+    pub name: String,
+    /// The profile directory.
+    pub path: PathBuf,
+    /// The time of the last build with this profile.
+    pub last_build: SystemTime,
+    /// The space allocated to the profile directory, in bytes.
+    pub bytes: u64,
+    /// The space allocated to the incremental compilation cache of the profile, in bytes.
+    pub incremental_bytes: u64,
+}
+
+/// The target directory of a Cargo project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TargetDir {
+    /// The project directory holding `Cargo.toml`.
+    pub project: PathBuf,
+    /// The target directory.
+    pub path: PathBuf,
+    /// The time of the last build with any profile.
+    pub last_build: SystemTime,
+    /// The space allocated to the target directory, in bytes.
+    pub bytes: u64,
+    /// The build profiles, in path order.
+    pub profiles: Vec<Profile>,
+}
+
+/// The directories to remove from a single target directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Cleanup {
+    /// The project directory holding `Cargo.toml`.
+    pub project: PathBuf,
+    /// The directories to remove.
+    pub paths: Vec<PathBuf>,
+    /// The space allocated to these directories, in bytes.
+    pub bytes: u64,
+}
+
+/// Returns `true` if `path` is the target directory of a Cargo project: a directory tagged with
+/// `CACHEDIR.TAG` next to a `Cargo.toml`.
+pub fn is_target_dir(path: &Path) -> bool {
+    let has_manifest = path.parent().is_some_and(|project| project.join("Cargo.toml").is_file());
+    has_manifest
+        && fs::read_to_string(path.join("CACHEDIR.TAG")).is_ok_and(|tag| tag.starts_with(CACHEDIR_TAG_SIGNATURE))
+}
+
+/// Finds the target directories below `roots`, most recently built first.
+///
+/// Hidden directories are skipped, and target directories are not searched further.
+pub fn find(roots: &[PathBuf]) -> Vec<TargetDir> {
+    let mut targets = Vec::new();
+    let mut pending: Vec<PathBuf> = roots.to_vec();
+    while let Some(dir) = pending.pop() {
+        if is_target_dir(&dir) {
+            targets.push(inspect(&dir));
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            if is_dir && !entry.file_name().to_string_lossy().starts_with('.') {
+                pending.push(entry.path());
+            }
+        }
+    }
+    targets.sort_by(|a, b| b.last_build.cmp(&a.last_build).then_with(|| a.path.cmp(&b.path)));
+    targets
+}
+
+/// Reads the profiles and sizes of the target directory at `path`.
+fn inspect(path: &Path) -> TargetDir {
+    let mut profiles = Vec::new();
+    for (name, dir) in subdirectories(path) {
+        if dir.join(".fingerprint").is_dir() {
+            profiles.push(profile(name, dir));
+            continue;
+        }
+        // Cross-compiled output is nested below the target triple, e.g. `aarch64-unknown-linux-gnu/debug`.
+        for (profile_name, profile_dir) in subdirectories(&dir) {
+            if profile_dir.join(".fingerprint").is_dir() {
+                profiles.push(profile(format!("{name}/{profile_name}"), profile_dir));
+            }
+        }
+    }
+    profiles.sort_by(|a, b| a.path.cmp(&b.path));
+
+    TargetDir {
+        project: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        path: path.to_path_buf(),
+        last_build: profiles.iter().map(|profile| profile.last_build).max().unwrap_or_else(|| modified(path)),
+        bytes: allocated(path),
+        profiles,
+    }
+}
+
+/// Reads the profile directory `path`, named `name`.
+fn profile(name: String, path: PathBuf) -> Profile {
+    // Cargo touches the fingerprints of every unit it checks, so the newest of them is the last build.
+    let fingerprints = path.join(".fingerprint");
+    let entries = fs::read_dir(&fingerprints).into_iter().flatten().flatten();
+    let last_build = entries.map(|entry| modified(&entry.path())).chain([modified(&fingerprints)]).max();
+    Profile {
+        name,
+        last_build: last_build.unwrap_or(SystemTime::UNIX_EPOCH),
+        bytes: allocated(&path),
+        incremental_bytes: allocated(&path.join("incremental")),
+        path,
+    }
+}
+
+/// Returns the names and paths of the directories directly inside `dir`.
+fn subdirectories(dir: &Path) -> Vec<(String, PathBuf)> {
+    let entries = fs::read_dir(dir).into_iter().flatten().flatten();
+    entries
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path()))
+        .collect()
+}
+
+/// Returns the modification time of `path`, or the epoch if it cannot be read.
+fn modified(path: &Path) -> SystemTime {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+/// Returns the space allocated to everything below `path`, or zero if it cannot be read.
+fn allocated(path: &Path) -> u64 {
+    let options = ScanOptions { one_file_system: false, top: 0 };
+    analyze::scan(path, options).map_or(0, |analysis| analysis.root.size.allocated)
+}
+
+/// Decides what to remove at time `now` from target directories not built within `older_than`.
+///
+/// Whole target directories are removed, unless `prune` is set. Pruning instead removes the
+/// profiles not built within `older_than` and the incremental compilation cache of the others,
+/// keeping recent build output usable.
+pub fn select(targets: &[TargetDir], older_than: Duration, prune: bool, now: SystemTime) -> Vec<Cleanup> {
+    let is_stale = |last_build: SystemTime| now.duration_since(last_build).unwrap_or_default() > older_than;
+    let mut cleanups = Vec::new();
+    for target in targets {
+        let mut cleanup = Cleanup { project: target.project.clone(), paths: Vec::new(), bytes: 0 };
+        if !prune {
+            if is_stale(target.last_build) {
+                cleanup.paths.push(target.path.clone());
+                cleanup.bytes = target.bytes;
+            }
+        } else {
+            for profile in &target.profiles {
+                if is_stale(profile.last_build) {
+                    cleanup.paths.push(profile.path.clone());
+                    cleanup.bytes += profile.bytes;
+                } else if profile.incremental_bytes > 0 {
+                    cleanup.paths.push(profile.path.join("incremental"));
+                    cleanup.bytes += profile.incremental_bytes;
+                }
+            }
+        }
+        if !cleanup.paths.is_empty() {
+            cleanups.push(cleanup);
+        }
+    }
+    cleanups
+}
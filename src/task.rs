@@ -0,0 +1,507 @@
+//! The task registry.
+//!
+//! A task is a cleanup that can run with its defaults, without any arguments, such as cleaning the
+//! package cache. `TASKS` lists every task in menu order and is the single place that decides what
+//! the menu offers: the menu keys, `Commands::from_selection`, the titles of the task commands and
+//! the extended help of the subcommands are all derived from it. Tasks have no `run` of their own:
+//! `command` and `unattended` return the `Commands` variant of the task, and `Commands::execute`
+//! runs it, so the command line, the menu and `auto` share one implementation.
+//!
+//! When the menu opens, every task estimates the space it would reclaim with its defaults. The
+//! estimates only query the system, and run concurrently since some of them walk large directory
+//! trees or wait for the package manager.
+
+use std::{
+    fmt, fs, mem,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::{
+    analyze::{self, ScanOptions},
+    cli::{self, Commands},
+    devcache, held, journal, logs,
+    procfs::PROC_DIR,
+    safeguard::{ProtectedKernels, BOOT_DIR},
+    session::Session,
+    version,
+};
+
+/// The directory holding the modules of each installed kernel, one directory per release.
+const MODULES_DIR: &str = "/lib/modules";
+
+/// The directory most of the space of installed packages is on.
+const PACKAGES_DIR: &str = "/usr";
+
+/// How much harm a task can do if it removes something that was still needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    /// Only removes data that is recreated on demand, such as caches and old logs.
+    Low,
+    /// Removes data that takes some effort to get back, or interrupts running services.
+    Medium,
+    /// Removes software the user may still rely on.
+    High,
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Risk::Low => "low",
+            Risk::Medium => "medium",
+            Risk::High => "high",
+        })
+    }
+}
+
+/// A cleanup that can be offered in the menu.
+pub trait Task: Sync {
+    /// Returns the command that runs the task with its defaults, as selected in the menu.
+    fn command(&self) -> Commands;
+
+    /// Returns the ID of the task, which is also the name of its subcommand, e.g.
+    /// `clean-package-cache`.
+    fn id(&self) -> &'static str;
+
+    /// Returns the human readable title of the task, as shown in the menu and as the title of its
+    /// command.
+    fn title(&self) -> &'static str;
+
+    /// Returns a sentence describing what the task removes, shown as help in the menu and in
+    /// `--help`.
+    fn description(&self) -> &'static str;
+
+    /// Returns how much harm the task can do.
+    fn risk(&self) -> Risk;
+
+    /// Returns the command that runs the task with its defaults without asking anything, as
+    /// `reclaim` and `auto` run it, or `None` if what the task removes must be picked by the user.
+    ///
+    /// By default this is `command`, for tasks that never prompt.
+    fn unattended(&self, _session: &Session) -> Option<Commands> {
+        Some(self.command())
+    }
+
+    /// Returns a directory on the filesystem the task frees space on, or `None` if the space can
+    /// be on any filesystem.
+    fn location(&self, _session: &Session) -> Option<PathBuf> {
+        None
+    }
+
+    /// Estimates the space the task would reclaim, in bytes, or returns `None` if it cannot tell.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if querying the system fails.
+    fn estimate(&self, _session: &Session) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// Removes installed packages picked from a list.
+#[derive(Debug, Clone, Copy)]
+pub struct RemovePackages;
+
+impl Task for RemovePackages {
+    fn id(&self) -> &'static str {
+        "remove-package"
+    }
+
+    fn title(&self) -> &'static str {
+        "Remove unnecessary packages"
+    }
+
+    fn command(&self) -> Commands {
+        // Without package names the command asks which installed packages to remove.
+        Commands::RemovePackage { packages: Vec::new() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes the installed packages you pick from a list sorted by size."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::High
+    }
+
+    fn unattended(&self, _session: &Session) -> Option<Commands> {
+        None
+    }
+
+    fn location(&self, _session: &Session) -> Option<PathBuf> {
+        Some(PathBuf::from(PACKAGES_DIR))
+    }
+}
+
+/// Cleans the download cache of the package manager.
+#[derive(Debug, Clone, Copy)]
+pub struct CleanPackageCache;
+
+impl Task for CleanPackageCache {
+    fn id(&self) -> &'static str {
+        "clean-package-cache"
+    }
+
+    fn title(&self) -> &'static str {
+        "Clean package cache"
+    }
+
+    fn command(&self) -> Commands {
+        Commands::CleanPackageCache
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes downloaded package files, which the package manager fetches again when needed."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Low
+    }
+
+    fn location(&self, session: &Session) -> Option<PathBuf> {
+        Some(PathBuf::from(session.package_manager.cache_dir()))
+    }
+
+    fn estimate(&self, session: &Session) -> Result<Option<u64>> {
+        let cache_dir = Path::new(session.package_manager.cache_dir());
+        let analysis = analyze::scan(cache_dir, ScanOptions { one_file_system: true, top: 0 })?;
+        Ok(Some(analysis.root.size.allocated))
+    }
+}
+
+/// Removes packages that were installed as dependencies and are no longer needed.
+#[derive(Debug, Clone, Copy)]
+pub struct UninstallUnusedApps;
+
+impl Task for UninstallUnusedApps {
+    fn id(&self) -> &'static str {
+        "uninstall-unused-apps"
+    }
+
+    fn title(&self) -> &'static str {
+        "Uninstall unused applications"
+    }
+
+    fn command(&self) -> Commands {
+        Commands::UninstallUnusedApps
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes packages that were installed as dependencies of packages that are gone."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Medium
+    }
+
+    fn location(&self, _session: &Session) -> Option<PathBuf> {
+        Some(PathBuf::from(PACKAGES_DIR))
+    }
+
+    fn estimate(&self, session: &Session) -> Result<Option<u64>> {
+        let package_manager = session.package_manager.as_ref();
+        let unneeded = package_manager.unneeded(&session.runner)?;
+        if unneeded.is_empty() {
+            return Ok(Some(0));
+        }
+        let installed = package_manager.list_installed(&session.runner)?;
+        Ok(Some(installed.iter().filter(|package| unneeded.contains(&package.name)).map(|package| package.size).sum()))
+    }
+}
+
+/// Removes all but the newest kernels.
+#[derive(Debug, Clone, Copy)]
+pub struct RemoveOldKernels;
+
+impl Task for RemoveOldKernels {
+    fn id(&self) -> &'static str {
+        "remove-old-kernels"
+    }
+
+    fn title(&self) -> &'static str {
+        "Remove old kernel versions"
+    }
+
+    fn command(&self) -> Commands {
+        Commands::RemoveOldKernels { keep: None }
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes all but the newest kernels. The running kernel and the default boot entry are kept."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Medium
+    }
+
+    fn unattended(&self, session: &Session) -> Option<Commands> {
+        Some(Commands::RemoveOldKernels {
+            keep: Some(session.config.kernels.keep.unwrap_or(cli::DEFAULT_KEPT_KERNELS)),
+        })
+    }
+
+    fn location(&self, _session: &Session) -> Option<PathBuf> {
+        Some(PathBuf::from(MODULES_DIR))
+    }
+
+    fn estimate(&self, session: &Session) -> Result<Option<u64>> {
+        let package_manager = session.package_manager.as_ref();
+        let mut kernels = package_manager.list_kernels(&session.runner)?;
+        kernels.sort_by(|a, b| version::rpmvercmp(a, b));
+        let protected = ProtectedKernels::detect(&session.runner, Path::new(BOOT_DIR))?;
+
+        // As without a terminal, all but the newest kernels are removed, except the protected ones.
+        let keep = session.config.kernels.keep.unwrap_or(cli::DEFAULT_KEPT_KERNELS);
+        let removed: Vec<&String> = kernels[..kernels.len().saturating_sub(keep)]
+            .iter()
+            .filter(|kernel| protected.reason(package_manager, kernel).is_none())
+            .collect();
+        let releases = fs::read_dir(MODULES_DIR).into_iter().flatten().flatten();
+        let bytes = releases
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|release| removed.iter().any(|kernel| package_manager.is_kernel_release(kernel, release)))
+            .map(|release| kernel_bytes(&release, Path::new(MODULES_DIR), Path::new(BOOT_DIR)))
+            .sum();
+        Ok(Some(bytes))
+    }
+}
+
+/// Returns the space allocated to the kernel `release`: its modules below `modules_dir` and the
+/// files below `boot_dir` named after it, such as `vmlinuz-<release>` and
+/// `initramfs-<release>.img`.
+pub fn kernel_bytes(release: &str, modules_dir: &Path, boot_dir: &Path) -> u64 {
+    let options = ScanOptions { one_file_system: true, top: 0 };
+    let modules = analyze::scan(&modules_dir.join(release), options).map_or(0, |analysis| analysis.root.size.allocated);
+    let boot_files = fs::read_dir(boot_dir).into_iter().flatten().flatten().filter(|entry| {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        name.ends_with(release) || name.contains(&format!("{release}."))
+    });
+    let boot: u64 = boot_files
+        .filter_map(|entry| fs::symlink_metadata(entry.path()).ok())
+        .map(|metadata| metadata.blocks() * 512)
+        .sum();
+    modules + boot
+}
+
+/// Vacuums the journal and deletes, or compresses, rotated logs.
+#[derive(Debug, Clone, Copy)]
+pub struct CleanUpLogFiles;
+
+impl Task for CleanUpLogFiles {
+    fn id(&self) -> &'static str {
+        "clean-up-log-files"
+    }
+
+    fn title(&self) -> &'static str {
+        "Clean up log files"
+    }
+
+    fn command(&self) -> Commands {
+        Commands::CleanUpLogFiles {
+            vacuum_time: None,
+            vacuum_size: None,
+            vacuum_files: None,
+            rotate: false,
+            log_max_age: None,
+            log_max_size: None,
+            no_compress: false,
+            journal_only: false,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes old journal entries and rotated logs below /var/log, and compresses the rest."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Low
+    }
+
+    fn unattended(&self, session: &Session) -> Option<Commands> {
+        let journal = &session.config.journal;
+        // A configured limit is enough on its own, otherwise the journal is vacuumed by age.
+        let limited = journal.vacuum_time.is_some() || journal.vacuum_size.is_some() || journal.vacuum_files.is_some();
+        Some(Commands::CleanUpLogFiles {
+            vacuum_time: if limited { None } else { Some(cli::DEFAULT_VACUUM_DAYS) },
+            vacuum_size: None,
+            vacuum_files: None,
+            rotate: false,
+            log_max_age: None,
+            log_max_size: None,
+            no_compress: false,
+            // Nobody reviews the rotated logs to delete, so they are left alone unless enabled.
+            journal_only: !session.config.logs.unattended.unwrap_or(false),
+        })
+    }
+
+    fn location(&self, _session: &Session) -> Option<PathBuf> {
+        Some(PathBuf::from(logs::LOG_DIR))
+    }
+
+    fn estimate(&self, session: &Session) -> Result<Option<u64>> {
+        // Vacuuming keeps the active journal files, so this is an upper bound.
+        Ok(journal::parse_bytes(&journal::disk_usage(&session.runner)?))
+    }
+}
+
+/// Frees the space of deleted files that processes still hold open.
+#[derive(Debug, Clone, Copy)]
+pub struct HeldSpace;
+
+impl Task for HeldSpace {
+    fn id(&self) -> &'static str {
+        "held-space"
+    }
+
+    fn title(&self) -> &'static str {
+        "Free space held by deleted files"
+    }
+
+    fn command(&self) -> Commands {
+        Commands::HeldSpace { action: None, all: false }
+    }
+
+    fn description(&self) -> &'static str {
+        "Truncates deleted files that processes still hold open, or restarts their services."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Medium
+    }
+
+    fn unattended(&self, _session: &Session) -> Option<Commands> {
+        // Truncating the files or restarting their services interrupts the processes holding them,
+        // so the user picks them.
+        None
+    }
+
+    fn estimate(&self, _session: &Session) -> Result<Option<u64>> {
+        Ok(Some(held::scan(Path::new(PROC_DIR)).bytes))
+    }
+}
+
+/// Removes the caches of developer tools.
+#[derive(Debug, Clone, Copy)]
+pub struct CleanDevCaches;
+
+impl Task for CleanDevCaches {
+    fn id(&self) -> &'static str {
+        "clean-dev-caches"
+    }
+
+    fn title(&self) -> &'static str {
+        "Clean developer caches"
+    }
+
+    fn command(&self) -> Commands {
+        Commands::CleanDevCaches {
+            tools: Vec::new(),
+            all: false,
+            roots: Vec::new(),
+            stale_days: devcache::DEFAULT_STALE_DAYS,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes the caches of Cargo, npm, pip, Go and other tools, which the next build recreates."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Low
+    }
+
+    fn unattended(&self, _session: &Session) -> Option<Commands> {
+        // Only the caches of the tools, never project directories below whatever directory the
+        // scheduler runs in.
+        let tools = devcache::TOOLS.iter().filter(|tool| !devcache::PROJECT_TOOLS.contains(tool));
+        Some(Commands::CleanDevCaches {
+            tools: tools.map(|tool| tool.name.to_string()).collect(),
+            all: false,
+            roots: Vec::new(),
+            stale_days: devcache::DEFAULT_STALE_DAYS,
+        })
+    }
+
+    fn location(&self, _session: &Session) -> Option<PathBuf> {
+        std::env::var_os("HOME").map(PathBuf::from)
+    }
+
+    fn estimate(&self, _session: &Session) -> Result<Option<u64>> {
+        // Only the caches of the tools count: searching the current directory for projects could
+        // walk a whole tree, such as `/`, before the menu appears.
+        let caches = devcache::known_caches(|name| std::env::var_os(name).map(PathBuf::from));
+        Ok(Some(devcache::measure(&caches).iter().map(|cache| cache.bytes).sum()))
+    }
+}
+
+/// Removes old temporary files with `systemd-tmpfiles`.
+#[derive(Debug, Clone, Copy)]
+pub struct CleanTempFiles;
+
+impl Task for CleanTempFiles {
+    fn id(&self) -> &'static str {
+        "clean-temp-files"
+    }
+
+    fn title(&self) -> &'static str {
+        "Clean temporary files"
+    }
+
+    fn command(&self) -> Commands {
+        Commands::CleanTempFiles
+    }
+
+    fn description(&self) -> &'static str {
+        "Removes files below /tmp and /var/tmp that are older than the ages set in tmpfiles.d."
+    }
+
+    fn risk(&self) -> Risk {
+        Risk::Low
+    }
+}
+
+/// Every task, in menu order. The menu key of a task is its position, starting at 1.
+pub const TASKS: [&dyn Task; 8] = [
+    &RemovePackages,
+    &CleanPackageCache,
+    &UninstallUnusedApps,
+    &RemoveOldKernels,
+    &CleanUpLogFiles,
+    &HeldSpace,
+    &CleanDevCaches,
+    &CleanTempFiles,
+];
+
+/// Returns the task with the menu key `key`, if there is one.
+pub fn by_key(key: usize) -> Option<&'static dyn Task> {
+    TASKS.get(key.checked_sub(1)?).copied()
+}
+
+/// Returns the task that runs through the variant of `command`, if there is one.
+pub fn of(command: &Commands) -> Option<&'static dyn Task> {
+    TASKS.iter().copied().find(|task| mem::discriminant(&task.command()) == mem::discriminant(command))
+}
+
+/// Returns the task with the ID `id`, if there is one.
+pub fn by_id(id: &str) -> Option<&'static dyn Task> {
+    TASKS.iter().copied().find(|task| task.id() == id)
+}
+
+/// Estimates the space each of `tasks` would reclaim, running the estimates concurrently. Tasks
+/// whose estimate fails or is unknown get `None`.
+pub fn estimates(session: &Session, tasks: &[&dyn Task]) -> Vec<Option<u64>> {
+    let estimates = thread::scope(|scope| {
+        let handles: Vec<_> =
+            tasks.iter().map(|task| scope.spawn(move || task.estimate(session).ok().flatten())).collect();
+        handles.into_iter().map(|handle| handle.join().ok().flatten()).collect()
+    });
+    // The queries belong to no task, so they are kept out of the report.
+    session.runner.take_records();
+    estimates
+}
@@ -1,4 +1,5 @@
 use std::{
+    env::consts::OS,
     fs::File,
     io::{self, Write},
 };
@@ -10,31 +11,46 @@ use pretty_assertions::{assert_eq, assert_str_eq};
 use tempfile::NamedTempFile;
 
 use super::*;
+use runner::{CmdOutput, CommandRunner, FakeRunner};
 #[cfg_attr(test, automock)]
 trait MyTrait {
     fn foo(&self, x: u32) -> u32;
 }
 
-fn main_with_cli(cli: cli::Cli) -> Result<()> {
+fn main_with_cli(cli: cli::Cli, runner: FakeRunner, selections: &[i32]) -> Result<report::Report> {
     let current_os = OS;
     println!("Welcome to disk space optimizer CLI for {current_os}!",);
 
-    match &cli.command {
-        Some(command) => command.execute()?,
+    let backend = cli.backend.unwrap_or(backend::Backend::Dnf);
+    let session = session::Session {
+        dry_run: cli.dry_run,
+        format: cli.format,
+        assume_yes: cli.yes,
+        quarantine: cli.quarantine,
+        ..session::Session::new(Box::new(runner), backend)
+    };
+
+    let mut report = report::Report::new(session.dry_run);
+    let result = match &cli.command {
+        Some(command) => execute_and_measure(command, &session, &mut report),
         _ => {
-            let commands = get_commands();
-            let selections = multidialogue::run_dialoguer(&commands)?;
+            // The menu needs a terminal, so the test passes the keys a user would have selected.
+            let commands = get_commands(&session.config);
+            let selections = commands.into_iter().filter(|item| selections.contains(&item.key));
 
             for selection in selections.into_iter() {
                 if let Some(command) = cli::Commands::from_selection(selection.key as usize) {
-                    if let Err(err) = command.execute() {
+                    if let Err(err) = execute_and_measure(&command, &session, &mut report) {
                         println!("Error: {err}", err = anyhow!(err));
                     }
                 }
             }
+            Ok(())
         }
-    }
-    Ok(())
+    };
+    report.print(session.format)?;
+    record_run(cli.command.as_ref(), &session, &report)?;
+    result.map(|()| report)
 }
 
 #[test]
@@ -49,13 +65,108 @@ fn mytest() {
 fn test_main() {
     // Test with command argument. It should not use dialoguer.
     let cli_with_command = cli::Cli {
-        command: Some(cli::Commands::RemoveOldKernels), // command: Some(Box::new(MockCommand {})),
+        backend: Some(backend::Backend::Dnf),
+        dry_run: false,
+        format: output::Format::Text,
+        yes: true,
+        non_interactive: true,
+        quarantine: false,
+        command: Some(cli::Commands::UninstallUnusedApps), // command: Some(Box::new(MockCommand {})),
     };
-    assert!(main_with_cli(cli_with_command).is_ok());
+    let runner =
+        FakeRunner::new().expect(&["sudo", "dnf", "autoremove", "--assumeyes"], CmdOutput::ok("Nothing to do."));
+    assert!(main_with_cli(cli_with_command, runner, &[]).is_ok());
 
     // Test without command argument. It should use dialoguer.
-    let cli_without_command = cli::Cli { command: None };
-    assert!(main_with_cli(cli_without_command).is_ok());
+    let cli_without_command = cli::Cli {
+        backend: Some(backend::Backend::Dnf),
+        dry_run: false,
+        format: output::Format::Text,
+        yes: true,
+        non_interactive: false,
+        quarantine: false,
+        command: None,
+    };
+    let runner = FakeRunner::new()
+        .expect(&["sudo", "dnf", "clean", "all"], CmdOutput::ok("42 files removed"))
+        .expect(&["sudo", "dnf", "autoremove", "--assumeyes"], CmdOutput::ok("Nothing to do."));
+    assert!(main_with_cli(cli_without_command, runner, &[2, 3, 0]).is_ok());
+}
+
+#[test]
+fn test_execute_with_fake_runner() -> Result<()> {
+    let runner = FakeRunner::new()
+        .expect(&["sudo", "apt-get", "clean"], CmdOutput::ok(""))
+        .expect(&["du", "-sh", "/var/cache/apt/archives"], CmdOutput::ok("8.0K\t/var/cache/apt/archives"));
+    let session = session::Session::new(Box::new(runner), backend::Backend::Apt);
+
+    cli::Commands::CleanPackageCache.execute(&session)?;
+    let dry_run = session::Session { dry_run: true, ..session };
+    cli::Commands::CleanPackageCache.execute(&dry_run)?;
+
+    // An unexpected command fails instead of touching the system.
+    let session = session::Session { dry_run: false, ..dry_run };
+    assert!(cli::Commands::UninstallUnusedApps.execute(&session).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_json_report() -> Result<()> {
+    let cli = cli::Cli {
+        backend: Some(backend::Backend::Dnf),
+        dry_run: false,
+        format: output::Format::Json,
+        yes: true,
+        non_interactive: true,
+        quarantine: false,
+        command: Some(cli::Commands::UninstallUnusedApps),
+    };
+    let runner =
+        FakeRunner::new().expect(&["sudo", "dnf", "autoremove", "--assumeyes"], CmdOutput::failed(1, "Error: locked"));
+    assert!(main_with_cli(cli, runner, &[]).is_err());
+
+    let mut report = report::Report::new(false);
+    let runner = runner::RecordingRunner::new(Box::new(
+        FakeRunner::new().expect(&["sudo", "dnf", "autoremove"], CmdOutput::failed(1, "Error: locked")),
+    ));
+    let result = report.run(
+        "Uninstall unused applications",
+        None,
+        || {
+            execute_cmd(&runner, "sudo", &["dnf", "autoremove"])?;
+            Ok(None)
+        },
+        || runner.take_records(),
+    );
+    assert!(result.is_err());
+
+    let document = serde_json::to_value(&report)?;
+    let task = &document["tasks"][0];
+    assert_eq!(task["task"], "Uninstall unused applications");
+    assert_eq!(task["commands"][0]["argv"], serde_json::json!(["sudo", "dnf", "autoremove"]));
+    assert_eq!(task["commands"][0]["exit_code"], 1);
+    assert_eq!(task["commands"][0]["stderr"], "Error: locked");
+    assert_eq!(task["bytes_reclaimed"], serde_json::Value::Null);
+    assert_eq!(task["errors"][0], "Command failed with exit code 1: Error: locked");
+    assert_eq!(report.to_json()["tasks"], document["tasks"]);
+    assert_eq!(report.to_json()["bytes_reclaimed"], 0);
+    Ok(())
+}
+
+#[test]
+fn test_pacman_autoremove_without_orphans() -> Result<()> {
+    let runner = FakeRunner::new().expect(&["pacman", "-Qdtq"], CmdOutput::failed(1, ""));
+    let step = backend::Backend::Pacman.package_manager().autoremove(&runner)?;
+    assert_eq!(step, None);
+    assert!(runner.is_done());
+
+    // Other failures are not mistaken for having no orphans.
+    let runner = FakeRunner::new()
+        .expect(&["pacman", "-Qdtq"], CmdOutput::failed(1, "error: failed to initialize alpm library"));
+    let err = backend::Backend::Pacman.package_manager().unneeded(&runner).unwrap_err();
+    assert!(err.to_string().contains("failed to initialize alpm library"), "{err}");
+    assert!(backend::Backend::Pacman.package_manager().unneeded(&FakeRunner::new()).is_err());
+    Ok(())
 }
 
 #[test]
@@ -81,11 +192,1250 @@ fn test_tempfile_dependecy() -> io::Result<()> {
 
 #[test]
 fn test_execute_cmd() -> Result<()> {
-    let output = execute_cmd("echo", &["hello", "world"])?;
+    let runner = FakeRunner::new()
+        .expect(&["echo", "hello", "world"], CmdOutput::ok("hello world\n"))
+        .expect(&["git", "status"], CmdOutput::failed(128, "fatal: not a git repository"));
+
+    // The messages go to the installed reporter. Other tests may report at the same time.
+    struct Collect(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+    impl output::Reporter for Collect {
+        fn say(&self, message: std::fmt::Arguments<'_>) {
+            self.0.lock().unwrap().push(message.to_string());
+        }
+    }
+    let messages = std::sync::Arc::default();
+    output::set_reporter(Some(Box::new(Collect(std::sync::Arc::clone(&messages)))));
+    let output = execute_cmd(&runner, "echo", &["hello", "world"])?;
+    output::set_reporter(None);
     assert_eq!(output, "hello world");
+    assert!(messages.lock().unwrap().contains(&String::from("Executing: echo hello world")));
+
+    let err = execute_cmd(&runner, "git", &["status"]).unwrap_err();
+    assert_str_eq!(err.to_string(), "Command failed with exit code 128: fatal: not a git repository");
+    assert!(runner.is_done());
+    Ok(())
+}
+
+#[test]
+fn test_system_runner() -> Result<()> {
+    let output = runner::SystemRunner.run("cat", &[], Some(b"hello world"))?;
+    assert!(output.success());
+    assert_eq!(output.stdout, "hello world");
+    Ok(())
+}
+
+#[test]
+fn test_backend_from_os_release() {
+    use backend::Backend;
+
+    let fedora = "NAME=\"Fedora Linux\"\nID=fedora\nVERSION_ID=38\n";
+    assert_eq!(Backend::from_os_release(fedora), Some(Backend::Dnf));
+
+    let mint = "ID=linuxmint\nID_LIKE=\"ubuntu debian\"\n";
+    assert_eq!(Backend::from_os_release(mint), Some(Backend::Apt));
+
+    let endeavour = "ID=endeavouros\nID_LIKE=arch\n";
+    assert_eq!(Backend::from_os_release(endeavour), Some(Backend::Pacman));
+
+    let tumbleweed = "ID=\"opensuse-tumbleweed\"\nID_LIKE=\"opensuse suse\"\n";
+    assert_eq!(Backend::from_os_release(tumbleweed), Some(Backend::Zypper));
+
+    assert_eq!(Backend::from_os_release("ID=haiku\n"), None);
+}
+
+#[test]
+fn test_parse_zypper_unneeded() {
+    let output = "\
+S | Repository | Name    | Version | Arch
+--+------------+---------+---------+-------
+i | repo-oss   | libfoo1 | 1.0-1.1 | x86_64
+i | repo-oss   | libbar2 | 2.3-2.1 | noarch
+";
+    assert_eq!(backend::parse_zypper_unneeded(output), vec!["libfoo1", "libbar2"]);
+
+    let output = "Reading package lists...\nThe following packages will be REMOVED:\n  libfoo1 libbar2\nRemv libfoo1 [1.0-1]\nRemv libbar2:i386 [2.3-2]\n";
+    assert_eq!(backend::parse_apt_simulated_removals(output), vec!["libfoo1", "libbar2"]);
+}
+
+#[test]
+fn test_parse_installed_packages() {
+    use std::time::{Duration, SystemTime};
+
+    let packages = backend::parse_rpm_packages(
+        "firefox\tx86_64\t115.0-1.fc38\t262144000\t1682935200\ngpg-pubkey\t(none)\t18b8e74c-62f2920f\t0\t1682935200\n",
+    );
+    assert_eq!(
+        packages,
+        vec![backend::Package {
+            name: String::from("firefox"),
+            arch: String::from("x86_64"),
+            version: String::from("115.0-1.fc38"),
+            size: 262_144_000,
+            installed: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_682_935_200)),
+            repo: None,
+        }]
+    );
+
+    // Header lines are not packages, and long names push the other columns onto the next line.
+    let repos = backend::parse_dnf_installed(
+        "Installed Packages\nfirefox.x86_64    115.0-1.fc38    @updates\nvery-long-package-name-for-testing.noarch\n                  1.0-1.fc38      @fedora\n",
+    );
+    let key = |name: &str, arch: &str| (name.to_string(), arch.to_string());
+    assert_eq!(repos.len(), 2);
+    assert_eq!(repos[&key("firefox", "x86_64")], "updates");
+    assert_eq!(repos[&key("very-long-package-name-for-testing", "noarch")], "fedora");
+
+    let packages = backend::parse_dpkg_packages(
+        "installed\tfirefox\tamd64\t115.0\t256000\t1682935200\nconfig-files\told\tamd64\t1.0\t\t\n",
+    );
+    assert_eq!(packages.len(), 1);
+    assert_eq!(packages[0].size, 256_000 * 1024);
+    let repos = backend::parse_apt_installed(
+        "Listing...\nfirefox/jammy-updates,now 115.0 amd64 [installed]\nlocal/now 1.0 all [installed,local]\n",
+    );
+    assert_eq!(repos.into_iter().collect::<Vec<_>>(), vec![(key("firefox", "amd64"), String::from("jammy-updates"))]);
+
+    let info = "Name            : firefox
+Version         : 115.0-1
+Architecture    : x86_64
+Installed Size  : 250.00 MiB
+Install Date    : Mon May  1 10:00:00 2023
+
+Name            : pacman-mirrorlist
+Version         : 20230410-1
+Architecture    : any
+Installed Size  : 25.32 KiB
+Install Date    : Sun Apr 30 09:00:00 2023
+";
+    let packages = backend::parse_pacman_info(info);
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[0].size, 250 * 1024 * 1024);
+    assert_eq!(packages[0].installed, Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_682_935_200)));
+    let repos = backend::parse_pacman_sync_list("extra firefox 115.0-1 [installed]\ncore pacman-mirrorlist 20230410-1 [installed: 20230401-1]\nextra chromium 114.0-1\n");
+    assert_eq!(repos[&key("pacman-mirrorlist", "any")], "core");
+    assert!(!repos.contains_key(&key("chromium", "any")));
+
+    let repos = backend::parse_zypper_installed("S | Repository | Name | Version | Arch\n--+---\ni | repo-oss | firefox | 115.0-1.1 | x86_64\ni | @System | local | 1.0 | noarch\n");
+    assert_eq!(repos.into_iter().collect::<Vec<_>>(), vec![(key("firefox", "x86_64"), String::from("repo-oss"))]);
+}
+
+#[test]
+fn test_plan_display() {
+    use plan::{Plan, Step};
+
+    let plan = Plan::new("Clean package cache")
+        .with_step(Step::sudo(&["dnf", "clean", "all"]).with_preview(&["du", "-sh", "/var/cache/dnf"]))
+        .with_optional_step(None);
+    assert_eq!(plan.steps.len(), 1);
+    assert_str_eq!(plan.steps[0].to_string(), "sudo dnf clean all");
+    assert_eq!(plan.steps[0].preview.as_deref(), Some(&["du", "-sh", "/var/cache/dnf"].map(String::from)[..]));
+
+    let remove = backend::Backend::Apt.package_manager().remove(&["foo", "bar"]);
+    assert_str_eq!(remove.to_string(), "sudo apt-get remove --assume-yes foo bar");
+}
+
+#[test]
+fn test_non_interactive() -> Result<()> {
+    let session = session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf);
+    assert!(session.confirm("remove the packages").is_err());
+    assert!(session.select("Select packages:", &["foo"], &[], "Pass them as arguments.").is_err());
+    assert_eq!(session.ask("Vacuum time:", 7)?, 7);
+
+    // Without package names or --keep, the removal commands need a selection and fail cleanly.
+    let runner = FakeRunner::new()
+        .expect(
+            &["rpm", "-qa", "--queryformat", backend::RPM_QUERY_FORMAT],
+            CmdOutput::ok("foo\tx86_64\t1.0-1\t1024\t0\n"),
+        )
+        .expect(&["dnf", "list", "--installed"], CmdOutput::ok("foo.x86_64 1.0-1 @fedora\n"));
+    let session = session::Session::new(Box::new(runner), backend::Backend::Dnf);
+    let err = cli::Commands::RemovePackage { packages: Vec::new() }.execute(&session).unwrap_err();
+    assert!(err.to_string().starts_with("Selection required"));
+
+    let runner = FakeRunner::new()
+        .expect(&["rpm", "-q", "kernel"], CmdOutput::ok("kernel-6.4.1\nkernel-6.4.2\nkernel-6.4.3\n"))
+        .expect(&["uname", "-r"], CmdOutput::ok("6.4.3\n"));
+    let session = session::Session::new(Box::new(runner), backend::Backend::Dnf);
+    let plan = cli::Commands::RemoveOldKernels { keep: Some(2) }.plan(&session)?;
+    assert_str_eq!(plan.steps[0].to_string(), "sudo dnf remove --assumeyes kernel-6.4.1");
+
+    // Explicit package names still need a confirmation before anything is removed.
+    let runner = FakeRunner::new().expect(&["uname", "-r"], CmdOutput::ok("6.4.3\n"));
+    let session = session::Session::new(Box::new(runner), backend::Backend::Dnf);
+    let command = cli::Commands::RemovePackage { packages: vec![String::from("foo")] };
+    assert!(command.execute(&session).unwrap_err().to_string().contains("--yes"));
+
+    let runner = FakeRunner::new()
+        .expect(&["uname", "-r"], CmdOutput::ok("6.4.3\n"))
+        .expect(&["sudo", "dnf", "remove", "--assumeyes", "foo"], CmdOutput::ok(""));
+    let session =
+        session::Session { assume_yes: true, ..session::Session::new(Box::new(runner), backend::Backend::Dnf) };
+    command.execute(&session)?;
+    Ok(())
+}
+
+#[test]
+fn test_rpmvercmp() {
+    use std::cmp::Ordering::{Equal, Greater, Less};
+    use version::rpmvercmp;
+
+    assert_eq!(rpmvercmp("6.10.1-200.fc40.x86_64", "6.9.12-200.fc40.x86_64"), Greater);
+    assert_eq!(rpmvercmp("6.5.0-0.rc1", "6.5.0-0.rc2"), Less);
+    assert_eq!(rpmvercmp("1.0010", "1.9"), Greater);
+    assert_eq!(rpmvercmp("1.05", "1.5"), Equal);
+    assert_eq!(rpmvercmp("1.0", "1.0.1"), Less);
+    assert_eq!(rpmvercmp("2.0a", "2.0.1"), Less);
+    assert_eq!(rpmvercmp("1.0~rc1", "1.0"), Less);
+    assert_eq!(rpmvercmp("1.0~rc1", "1.0~rc2"), Less);
+    assert_eq!(rpmvercmp("1.0^git1", "1.0"), Greater);
+    assert_eq!(rpmvercmp("1.0^git1", "1.0.1"), Less);
+    assert_eq!(rpmvercmp("1.0", "1_0"), Equal);
+}
+
+#[test]
+fn test_remove_old_kernels() -> Result<()> {
+    // `rpm -q` lists kernels in database order, not version order.
+    let kernels = "kernel-6.10.1-200.fc40.x86_64\nkernel-6.9.12-200.fc40.x86_64\nkernel-6.11.0-0.rc1.fc40.x86_64\n";
+    let runner = FakeRunner::new()
+        .expect(&["rpm", "-q", "kernel"], CmdOutput::ok(kernels))
+        .expect(&["uname", "-r"], CmdOutput::ok("6.11.0-0.rc1.fc40.x86_64\n"))
+        .expect(&["sudo", "dnf", "remove", "--assumeyes", "kernel-6.9.12-200.fc40.x86_64"], CmdOutput::ok(""));
+    let session =
+        session::Session { assume_yes: true, ..session::Session::new(Box::new(runner), backend::Backend::Dnf) };
+    cli::Commands::RemoveOldKernels { keep: None }.execute(&session)?;
+
+    // Keeping more kernels than are installed removes nothing.
+    let runner = FakeRunner::new()
+        .expect(&["rpm", "-q", "kernel"], CmdOutput::ok(kernels))
+        .expect(&["uname", "-r"], CmdOutput::ok("6.11.0-0.rc1.fc40.x86_64\n"));
+    let session = session::Session::new(Box::new(runner), backend::Backend::Dnf);
+    let plan = cli::Commands::RemoveOldKernels { keep: Some(5) }.plan(&session)?;
+    assert!(plan.steps.is_empty());
+
+    // The running kernel is never removed, even when it is old and --yes is given.
+    let runner = FakeRunner::new()
+        .expect(&["rpm", "-q", "kernel"], CmdOutput::ok(kernels))
+        .expect(&["uname", "-r"], CmdOutput::ok("6.9.12-200.fc40.x86_64\n"));
+    let session =
+        session::Session { assume_yes: true, ..session::Session::new(Box::new(runner), backend::Backend::Dnf) };
+    let plan = cli::Commands::RemoveOldKernels { keep: Some(0) }.plan(&session)?;
+    assert_str_eq!(
+        plan.steps[0].to_string(),
+        "sudo dnf remove --assumeyes kernel-6.10.1-200.fc40.x86_64 kernel-6.11.0-0.rc1.fc40.x86_64"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_protected_kernels() -> Result<()> {
+    use backend::Backend;
+    use safeguard::{default_boot_entry, ProtectedKernels};
+
+    let boot = tempfile::tempdir()?;
+    assert_eq!(default_boot_entry(boot.path()), None);
+
+    let entries = boot.path().join("loader/entries");
+    std::fs::create_dir_all(&entries)?;
+    for version in ["6.9.12-200.fc40.x86_64", "6.10.1-200.fc40.x86_64"] {
+        // Keys that merely start with `version` are not the version.
+        let entry =
+            format!("title Fedora Linux ({version})\nversion_note 99.0\nversion {version}\nlinux /vmlinuz-{version}\n");
+        std::fs::write(entries.join(format!("abc123-{version}.conf")), entry)?;
+    }
+    assert_eq!(default_boot_entry(boot.path()).as_deref(), Some("6.10.1-200.fc40.x86_64"));
+
+    std::fs::create_dir(boot.path().join("grub2"))?;
+    std::fs::write(
+        boot.path().join("grub2/grubenv"),
+        "# GRUB Environment Block\nsaved_entry=abc123-6.9.12-200.fc40.x86_64\n",
+    )?;
+    assert_eq!(default_boot_entry(boot.path()).as_deref(), Some("6.9.12-200.fc40.x86_64"));
+
+    let runner = FakeRunner::new().expect(&["uname", "-r"], CmdOutput::ok("6.10.1-200.fc40.x86_64\n"));
+    let protected = ProtectedKernels::detect(&runner, boot.path())?;
+    let dnf = Backend::Dnf.package_manager();
+    assert_eq!(protected.reason(dnf.as_ref(), "kernel-6.10.1-200.fc40.x86_64"), Some("running kernel"));
+    assert_eq!(protected.reason(dnf.as_ref(), "kernel-6.9.12-200.fc40.x86_64"), Some("default boot entry"));
+    assert_eq!(protected.reason(dnf.as_ref(), "kernel-6.1.12-200.fc40.x86_64"), None);
+
+    let apt = Backend::Apt.package_manager();
+    assert!(apt.is_kernel_release("linux-image-6.1.0-13-amd64", "6.1.0-13-amd64"));
+    assert!(!apt.is_kernel_release("linux-image-6.1.0-13-amd64", "1.0-13-amd64"));
+
+    let pacman = Backend::Pacman.package_manager();
+    assert!(pacman.is_kernel_release("linux", "6.9.1-arch1-1"));
+    assert!(pacman.is_kernel_release("linux-lts", "6.6.30-1-lts"));
+    assert!(pacman.is_kernel_release("linux-rt-lts", "6.6.30-rt30-1-rt-lts"));
+    assert!(!pacman.is_kernel_release("linux", "6.6.30-1-lts"));
+
+    let zypper = Backend::Zypper.package_manager();
+    assert!(zypper.is_kernel_release("kernel-default-6.4.0-150600.23.7.1.x86_64", "6.4.0-150600.23.7-default"));
+    assert!(!zypper.is_kernel_release("kernel-default-6.4.0-150600.23.71.1.x86_64", "6.4.0-150600.23.7-default"));
+    Ok(())
+}
+
+#[test]
+fn test_config() -> Result<()> {
+    use config::Config;
+
+    let dir = tempfile::tempdir()?;
+    let system = dir.path().join("system.toml");
+    let user = dir.path().join("user.toml");
+    std::fs::write(&system, "[journal]\nvacuum_time = 30\nvacuum_size = \"1G\"\n[packages]\nprotected = [\"vim\"]\n")?;
+    std::fs::write(
+        &user,
+        "[journal]\nvacuum_size = \"500M\"\n[kernels]\nkeep = 3\n[packages]\nprotected = [\"firefox\"]\n",
+    )?;
+
+    let config = Config::load_from(&[system.clone(), user.clone(), dir.path().join("missing.toml")])?;
+    assert_eq!(config.journal.vacuum_time, Some(30));
+    assert_eq!(config.journal.vacuum_size.as_deref(), Some("500M"));
+    assert_eq!(config.kernels.keep, Some(3));
+    assert_eq!(config.packages.protected, vec!["vim", "firefox"]);
+    assert!(config.is_enabled("clean-package-cache"));
+
+    std::fs::write(&user, "[tasks]\nenabled = [\"clean-package-cache\"]\n")?;
+    let config = Config::load_from(std::slice::from_ref(&user))?;
+    let keys: Vec<i32> = get_commands(&config).into_iter().map(|item| item.key).collect();
+    assert_eq!(keys, vec![2, 0]);
+
+    std::fs::write(&user, "[journal]\nvacuum_days = 3\n")?;
+    assert!(Config::load_from(&[user]).is_err());
+
+    // Configured values are defaults, which command-line flags override.
+    let mut config = Config::load_from(&[system])?;
+    config.logs.directory = Some(dir.path().join("no-logs"));
+    let session =
+        session::Session { config, ..session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf) };
+    let logs = |vacuum_time| cli::Commands::CleanUpLogFiles {
+        vacuum_time,
+        vacuum_size: None,
+        vacuum_files: None,
+        rotate: false,
+        log_max_age: None,
+        log_max_size: None,
+        no_compress: false,
+        journal_only: false,
+    };
+    let plan = logs(None).plan(&session)?;
+    assert_str_eq!(plan.steps[0].to_string(), "sudo journalctl --vacuum-time=30d --vacuum-size=1G");
+    let plan = logs(Some(2)).plan(&session)?;
+    assert_str_eq!(plan.steps[0].to_string(), "sudo journalctl --vacuum-time=2d --vacuum-size=1G");
+
+    let runner = FakeRunner::new().expect(&["uname", "-r"], CmdOutput::ok("6.4.3\n"));
+    let session = session::Session { runner: runner::RecordingRunner::new(Box::new(runner)), ..session };
+    let command = cli::Commands::RemovePackage { packages: vec![String::from("vim"), String::from("nano")] };
+    assert_str_eq!(command.plan(&session)?.steps[0].to_string(), "sudo dnf remove --assumeyes nano");
+    Ok(())
+}
+
+#[test]
+fn test_task_registry() {
+    use clap::{CommandFactory, Parser};
+    use task::{Risk, TASKS};
+
+    let mut ids: Vec<&str> = TASKS.iter().map(|task| task.id()).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), TASKS.len());
+
+    let command = cli::Cli::command_with_tasks();
+    for (key, task) in (1..).zip(TASKS) {
+        assert_eq!(cli::Commands::from_selection(key).map(|command| command.title()), Some(task.title()));
+        assert_eq!(task::by_id(task.id()).map(|task| task.title()), Some(task.title()));
+        // The ID names the subcommand of the variant the task runs through.
+        let parsed = cli::Cli::try_parse_from(["disk-space-optimizer", task.id()]).unwrap().command.unwrap();
+        assert_eq!(task::of(&parsed).map(|task| task.id()), Some(task.id()));
+        let subcommand = command.find_subcommand(task.id()).expect("every task is a subcommand");
+        assert!(subcommand.get_long_about().unwrap().to_string().ends_with(&format!("Risk: {}.", task.risk())));
+    }
+    assert!(cli::Commands::from_selection(0).is_none());
+    assert!(cli::Commands::from_selection(TASKS.len() + 1).is_none());
+    assert!(task::by_id("history").is_none());
+    assert_eq!(task::by_id("clean-package-cache").map(|task| task.risk()), Some(Risk::Low));
+    assert_eq!(cli::Cli::command().get_subcommands().count(), command.get_subcommands().count());
+
+    let items: Vec<_> = get_commands(&config::Config::default()).into_iter().collect();
+    assert_eq!(items.len(), TASKS.len() + 1);
+    assert!(items[0].help.as_deref().unwrap().ends_with("Risk: high."));
+    assert_eq!(items.last().map(|item| (item.key, item.help.is_none())), Some((0, true)));
+}
+
+#[test]
+fn test_task_estimates() -> Result<()> {
+    use task::Task;
+
+    let info = "Name            : libfoo
+Version         : 1.0-1
+Architecture    : x86_64
+Installed Size  : 2.00 MiB
+Install Date    : Mon May  1 10:00:00 2023
+
+Name            : firefox
+Version         : 115.0-1
+Architecture    : x86_64
+Installed Size  : 250.00 MiB
+Install Date    : Mon May  1 10:00:00 2023
+";
+    let runner = FakeRunner::new()
+        .expect(&["pacman", "-Qdtq"], CmdOutput::ok("libfoo\n"))
+        .expect(&["env", "LC_ALL=C", "pacman", "-Qi"], CmdOutput::ok(info))
+        .expect(&["pacman", "-Sl"], CmdOutput::ok(""));
+    let session = session::Session::new(Box::new(runner), backend::Backend::Pacman);
+    let estimates = task::estimates(&session, &[&task::UninstallUnusedApps, &task::RemovePackages]);
+    assert_eq!(estimates, vec![Some(2 * 1024 * 1024), None]);
+    assert!(session.runner.take_records().is_empty(), "estimates are not part of the report");
+
+    let runner = FakeRunner::new().expect(&["pacman", "-Qdtq"], CmdOutput::failed(1, ""));
+    let session = session::Session::new(Box::new(runner), backend::Backend::Pacman);
+    assert_eq!(task::UninstallUnusedApps.estimate(&session)?, Some(0));
+
+    let runner = FakeRunner::new().expect(
+        &["journalctl", "--disk-usage"],
+        CmdOutput::ok("Archived and active journals take up 1.5G in the file system."),
+    );
+    let session = session::Session::new(Box::new(runner), backend::Backend::Dnf);
+    assert_eq!(task::CleanUpLogFiles.estimate(&session)?, Some(1536 * 1024 * 1024));
+
+    let dir = tempfile::tempdir()?;
+    let (modules, boot) = (dir.path().join("modules"), dir.path().join("boot"));
+    std::fs::create_dir_all(modules.join("6.4.3/kernel"))?;
+    std::fs::create_dir_all(&boot)?;
+    std::fs::write(modules.join("6.4.3/kernel/ext4.ko"), vec![1; 8192])?;
+    std::fs::write(boot.join("vmlinuz-6.4.3"), vec![1; 8192])?;
+    std::fs::write(boot.join("vmlinuz-6.5.0"), vec![1; 8192])?;
+    std::fs::write(boot.join("vmlinuz-6.4.30"), vec![1; 8192])?;
+    let bytes = task::kernel_bytes("6.4.3", &modules, &boot);
+    assert!(bytes >= 16 * 1024, "{bytes}");
+    // Only the boot files of other releases are left out.
+    let other = task::kernel_bytes("6.5.0", &modules, &boot);
+    assert!((8 * 1024..bytes).contains(&other), "{other}");
+
+    let items = multidialogue::DiskSpaceOptimizerItems::new()
+        .with_option(multidialogue::SelectableItem {
+            estimate: Some(3 << 30),
+            ..multidialogue::SelectableItem::new(2, "Clean package cache")
+        })
+        .with_option(multidialogue::SelectableItem::new(0, "Exit"));
+    assert_eq!(multidialogue::format_prompt_item(&items)?, vec!["2: Clean package cache (~3.0 GiB)", "0: Exit"]);
+    Ok(())
+}
+
+#[test]
+fn test_reclaim() -> Result<()> {
+    use reclaim::Candidate;
+    use task::{Risk, Task};
+
+    let candidate = |id, risk, estimate| Candidate { id, title: id, risk, estimate };
+    let candidates = vec![
+        candidate("kernels", Risk::Medium, 800),
+        candidate("logs", Risk::Low, 100),
+        candidate("cache", Risk::Low, 300),
+        candidate("held", Risk::Medium, 0),
+        candidate("packages", Risk::High, 5000),
+    ];
+    let ids = |plan: Vec<Candidate>| plan.into_iter().map(|candidate| candidate.id).collect::<Vec<_>>();
+    assert_eq!(ids(reclaim::plan(candidates.clone(), 250)), vec!["cache"]);
+    assert_eq!(ids(reclaim::plan(candidates.clone(), 400)), vec!["cache", "logs"]);
+    assert_eq!(ids(reclaim::plan(candidates.clone(), 401)), vec!["cache", "logs", "kernels"]);
+    assert_eq!(ids(reclaim::plan(candidates.clone(), 1 << 40)), vec!["cache", "logs", "kernels", "packages"]);
+    assert!(reclaim::plan(candidates, 0).is_empty());
+
+    // Once approved, the tasks of the plan no longer ask.
+    let session = session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf);
+    assert!(session.confirm("Proceed").is_err());
+    session.approve();
+    assert!(session.confirm("Proceed")?);
+
+    let logs = task::CleanUpLogFiles.unattended(&session).unwrap();
+    assert!(matches!(
+        logs,
+        cli::Commands::CleanUpLogFiles { vacuum_time: Some(cli::DEFAULT_VACUUM_DAYS), journal_only: true, .. }
+    ));
+    let mut session = session;
+    session.config.journal.vacuum_size = Some(String::from("1G"));
+    let logs = task::CleanUpLogFiles.unattended(&session).unwrap();
+    assert!(matches!(logs, cli::Commands::CleanUpLogFiles { vacuum_time: None, .. }));
+
+    // Held space and packages are left to the user.
+    assert!(task::HeldSpace.unattended(&session).is_none());
+    assert!(task::RemovePackages.unattended(&session).is_none());
+
+    // Unattended, only the tool caches are removed, never project directories below the current one.
+    let caches = task::CleanDevCaches.unattended(&session).unwrap();
+    let cli::Commands::CleanDevCaches { tools, all: false, .. } = &caches else {
+        panic!("unexpected command: {caches:?}");
+    };
+    assert!(tools.contains(&String::from("cargo")) && !tools.contains(&String::from("cargo-targets")));
+    assert!(!caches.locations().contains(&std::env::current_dir()?));
+
+    let dir = tempfile::tempdir()?;
+    let usage = CmdOutput::ok("Archived and active journals take up 1.0G in the file system.");
+    let runner = FakeRunner::new()
+        .expect(&["journalctl", "--disk-usage"], usage.clone())
+        .expect(&["journalctl", "--disk-usage"], usage);
+    let mut session =
+        session::Session { dry_run: true, ..session::Session::new(Box::new(runner), backend::Backend::Dnf) };
+    session.config.tasks.enabled = Some(vec![String::from("clean-up-log-files")]);
+    session.config.logs.directory = Some(dir.path().to_path_buf());
+    let command =
+        cli::Commands::Reclaim { target: String::from("10GiB"), mount: std::path::PathBuf::from(logs::LOG_DIR) };
+    let outcome = command.execute(&session)?.unwrap();
+    assert_eq!(outcome["plan"][0]["id"], "clean-up-log-files");
+    assert_eq!(outcome["plan"][0]["estimate"], 1 << 30);
+    assert_eq!(outcome["ran"], serde_json::json!(["clean-up-log-files"]));
+    assert_eq!(outcome["reached"], false);
+    Ok(())
+}
+
+#[test]
+fn test_auto() -> Result<()> {
+    use auto::Status;
+    use task::Task;
+
+    assert_eq!(auto::parse_percent("85%")?, 85.0);
+    assert_eq!(auto::parse_percent("92.5")?, 92.5);
+    assert!(auto::parse_percent("101%").is_err());
+    assert!(auto::parse_percent("most").is_err());
+    let usage = space::Usage { total: 100, free: 20, available: 10 };
+    assert_eq!(format!("{:.1}", auto::used_percent(usage)), "88.9");
+    assert_eq!(auto::used_percent(space::Usage::default()), 0.0);
+    let codes = [Status::NothingToDo, Status::Cleaned, Status::StillAbove, Status::DryRun].map(Status::exit_code);
+    assert_eq!(codes, [0, 10, 11, 0]);
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, "[auto]\ntasks = [\"clean-temp-files\"]\n")?;
+    assert_eq!(config::Config::load_from(&[path])?.auto.tasks, Some(vec![String::from("clean-temp-files")]));
+
+    let session = session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf);
+    let mut report = report::Report::new(false);
+    let command = cli::Commands::Auto { when_used_above: String::from("100%"), mount: "/".into() };
+    execute_and_measure(&command, &session, &mut report)?;
+    assert_eq!(auto::exit_code(&report), Some(0));
+    assert!(report.tasks[0].commands.is_empty());
+
+    // Only the configured low-risk tasks run, without asking.
+    let runner = FakeRunner::new().expect(&["du", "-sh", "/tmp", "/var/tmp"], CmdOutput::ok("4.0K\t/tmp\n"));
+    let mut session =
+        session::Session { dry_run: true, ..session::Session::new(Box::new(runner), backend::Backend::Dnf) };
+    session.config.auto.tasks = Some(vec![String::from("clean-temp-files"), String::from("remove-old-kernels")]);
+    let mut report = report::Report::new(true);
+    let command = cli::Commands::Auto { when_used_above: String::from("0%"), mount: "/".into() };
+    execute_and_measure(&command, &session, &mut report)?;
+    let details = report.tasks[0].details.as_ref().unwrap();
+    assert_eq!(details["tasks"], serde_json::json!(["clean-temp-files"]));
+    assert_eq!(details["status"], "dry-run");
+    assert_eq!(auto::exit_code(&report), Some(0));
+
+    // Tasks disabled in the configuration do not run either.
+    let mut session = session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf);
+    session.dry_run = true;
+    session.config.auto.tasks = Some(vec![String::from("clean-temp-files")]);
+    session.config.tasks.enabled = Some(vec![String::from("clean-package-cache")]);
+    let details = command.execute(&session)?.unwrap();
+    assert_eq!(details["tasks"], serde_json::json!([]));
+
+    // Unattended, only the journal is vacuumed, and no rotated log is deleted or compressed unless
+    // enabled in the configuration.
+    let dir = tempfile::tempdir()?;
+    let old = dir.path().join("messages-20230501");
+    File::create(&old)?
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(90 * 24 * 60 * 60))?;
+    let mut session = session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf);
+    session.config.logs.directory = Some(dir.path().to_path_buf());
+    let programs = |session: &session::Session| -> Result<Vec<String>> {
+        let plan = task::CleanUpLogFiles.unattended(session).unwrap().plan(session)?;
+        Ok(plan.steps.iter().flat_map(|step| step.args.first().cloned()).collect())
+    };
+    assert_eq!(programs(&session)?, vec!["journalctl"]);
+    // Rotated logs are only considered when every process can be inspected, i.e. when running as root.
+    if procfs::OpenFiles::scan(std::path::Path::new(procfs::PROC_DIR)).inaccessible == 0 {
+        session.config.logs.unattended = Some(true);
+        assert_eq!(programs(&session)?, vec!["journalctl", "rm"]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_clean_up_journal() -> Result<()> {
+    let runner = FakeRunner::new()
+        .expect(
+            &["journalctl", "--disk-usage"],
+            CmdOutput::ok("Archived and active journals take up 3.9G in the file system.\n"),
+        )
+        .expect(&["sudo", "journalctl", "--rotate", "--vacuum-size=500M", "--vacuum-files=5"], CmdOutput::ok(""))
+        .expect(
+            &["journalctl", "--disk-usage"],
+            CmdOutput::ok("Archived and active journals take up 480.0M in the file system.\n"),
+        );
+    let mut session = session::Session::new(Box::new(runner), backend::Backend::Dnf);
+    session.config.logs.directory = Some(tempfile::tempdir()?.path().to_path_buf());
+    let command = cli::Commands::CleanUpLogFiles {
+        vacuum_time: None,
+        vacuum_size: Some(String::from("500M")),
+        vacuum_files: Some(5),
+        rotate: true,
+        log_max_age: None,
+        log_max_size: None,
+        no_compress: false,
+        journal_only: false,
+    };
+    let details = command.execute(&session)?;
+    assert_eq!(details, Some(serde_json::json!({ "disk_usage_before": "3.9G", "disk_usage_after": "480.0M" })));
+
+    // Deleting rotated logs needs a confirmation. They are only considered when every process can
+    // be inspected, i.e. when running as root.
+    if procfs::OpenFiles::scan(std::path::Path::new(procfs::PROC_DIR)).inaccessible == 0 {
+        let dir = tempfile::tempdir()?;
+        let old = dir.path().join("messages-20230501");
+        // The file is closed right away, as logs held open are left alone.
+        File::create(&old)?
+            .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(90 * 24 * 60 * 60))?;
+        let usage = CmdOutput::ok("Archived and active journals take up 3.9G in the file system.\n");
+        let runner = FakeRunner::new().expect(&["journalctl", "--disk-usage"], usage);
+        let mut session = session::Session::new(Box::new(runner), backend::Backend::Dnf);
+        session.config.logs.directory = Some(dir.path().to_path_buf());
+        let err = command.execute(&session).unwrap_err();
+        assert!(err.to_string().contains("Pass --yes"), "{err}");
+        assert!(old.exists());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rotated_logs() -> Result<()> {
+    use std::time::{Duration, SystemTime};
+
+    use logs::{is_rotated, Policy};
+    use procfs::{OpenFile, OpenFiles};
+
+    for name in ["messages.1", "syslog.2.gz", "secure-20230501", "Xorg.0.log.old", "dmesg.gz", "boot.log-20230501.xz"] {
+        assert!(is_rotated(name), "{name}");
+    }
+    for name in ["messages", "Xorg.0.log", "dpkg.log", "lastlog", ".old", "wtmp-1", "foo."] {
+        assert!(!is_rotated(name), "{name}");
+    }
+
+    let dir = tempfile::tempdir()?;
+    let now = SystemTime::now();
+    let day = Duration::from_secs(24 * 60 * 60);
+    std::fs::create_dir(dir.path().join("nginx"))?;
+    for (name, bytes, age) in [
+        ("messages", 100, 0),
+        ("messages-20230501", 400, 40),
+        ("messages-20230508", 300, 20),
+        ("nginx/access.log.1", 200, 2),
+        ("nginx/access.log.2.gz", 50, 9),
+        ("Xorg.0.log.old", 10, 1),
+    ] {
+        let file = std::fs::File::create(dir.path().join(name))?;
+        file.set_len(bytes)?;
+        file.set_modified(now - day * age)?;
+    }
+
+    let rotated = logs::find_rotated(dir.path());
+    assert_eq!(rotated.len(), 5);
+    let services: Vec<(String, u64, u64)> =
+        logs::by_service(&rotated).into_iter().map(|service| (service.service, service.files, service.bytes)).collect();
+    assert_eq!(
+        services,
+        vec![(String::from("messages"), 2, 700), (String::from("nginx"), 2, 250), (String::from("Xorg.0"), 1, 10)]
+    );
+
+    // Old logs are deleted, then the oldest until the rest fit, and the remaining ones compressed.
+    let held = OpenFile { pid: 42, fd: 3, target: dir.path().join("Xorg.0.log.old"), deleted: false };
+    let open_files = OpenFiles { files: vec![held.clone()], inaccessible: 0 };
+    let policy = Policy { max_age: day * 30, max_size: Some(250), compress: true };
+    let cleanup = logs::select(&rotated, &open_files, policy, now);
+    assert_eq!(cleanup.delete, vec![dir.path().join("messages-20230501"), dir.path().join("messages-20230508")]);
+    assert_eq!(cleanup.compress, vec![dir.path().join("nginx/access.log.1")]);
+    assert_eq!(cleanup.held_open, vec![held]);
+
+    assert_eq!(logs::parse_size("500M")?, 500 * 1024 * 1024);
+    assert_eq!(logs::parse_size("2G")?, 2 * 1024 * 1024 * 1024);
+    assert_eq!(logs::parse_size("4096")?, 4096);
+    assert_eq!(logs::parse_size("10GiB")?, 10 * 1024 * 1024 * 1024);
+    assert!(logs::parse_size("5 apples").is_err());
+    assert!(logs::parse_size("99999999999T").is_err());
+    assert!(logs::parse_size("99999999999999999999").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_open_files() -> Result<()> {
+    use std::path::Path;
+
+    use procfs::OpenFiles;
+
+    let proc_dir = tempfile::tempdir()?;
+    for (pid, fd, target) in
+        [("42", "3", "/var/log/messages"), ("42", "4", "socket:[1234]"), ("7", "1", "/tmp/big (deleted)")]
+    {
+        std::fs::create_dir_all(proc_dir.path().join(pid).join("fd"))?;
+        std::os::unix::fs::symlink(target, proc_dir.path().join(pid).join("fd").join(fd))?;
+    }
+    std::fs::create_dir(proc_dir.path().join("self"))?;
+
+    let open_files = OpenFiles::scan(proc_dir.path());
+    assert_eq!(open_files.files.len(), 2);
+    assert_eq!(open_files.inaccessible, 0);
+    assert_eq!(open_files.holder(Path::new("/var/log/messages")).map(|file| (file.pid, file.fd)), Some((42, 3)));
+    assert_eq!(open_files.holder(Path::new("/tmp/big")), None);
+    assert!(open_files.files.iter().any(|file| file.deleted && file.target == Path::new("/tmp/big")));
+    Ok(())
+}
+
+#[test]
+fn test_held_space() -> Result<()> {
+    use std::{
+        os::fd::{AsRawFd, FromRawFd},
+        path::Path,
+    };
+
+    // Deleted files on a tmpfs are not held disk space, and /tmp may be one.
+    let files = tempfile::tempdir_in(env!("CARGO_MANIFEST_DIR"))?;
+    // The link target of a deleted file carries the suffix, so a file named like it stands in for one.
+    let data = files.path().join("data.log (deleted)");
+    std::fs::write(&data, vec![b'x'; 64 * 1024])?;
+    let access = files.path().join("access.log (deleted)");
+    std::fs::write(&access, vec![b'x'; 16 * 1024])?;
+    let cache = files.path().join("cache.db (deleted)");
+    std::fs::write(&cache, vec![b'x'; 8 * 1024])?;
+
+    let proc_dir = tempfile::tempdir()?;
+    for (pid, fd, target, comm, cgroup) in [
+        ("42", "3", data.clone(), "nginx", "0::/system.slice/nginx.service"),
+        ("43", "5", data.clone(), "nginx", "0::/system.slice/nginx.service"),
+        ("44", "4", access.clone(), "nginx", "0::/system.slice/nginx.service"),
+        ("7", "3", cache.clone(), "app", "0::/user.slice/user-1000.slice/session-2.scope"),
+        ("7", "1", files.path().join("gone (deleted)"), "app", "0::/user.slice/user-1000.slice/session-2.scope"),
+        ("7", "2", files.path().join("kept.log"), "app", ""),
+    ] {
+        let process_dir = proc_dir.path().join(pid);
+        std::fs::create_dir_all(process_dir.join("fd"))?;
+        std::os::unix::fs::symlink(target, process_dir.join("fd").join(fd))?;
+        std::fs::write(process_dir.join("comm"), format!("{comm}\n"))?;
+        std::fs::write(process_dir.join("cgroup"), format!("{cgroup}\n"))?;
+    }
+
+    let held = held::scan(proc_dir.path());
+    let found: Vec<(u32, u32, &str, Option<&str>)> =
+        held.files.iter().map(|file| (file.pid, file.fd, file.process.as_str(), file.unit.as_deref())).collect();
+    assert_eq!(
+        found,
+        vec![
+            (42, 3, "nginx", Some("nginx.service")),
+            (43, 5, "nginx", Some("nginx.service")),
+            (44, 4, "nginx", Some("nginx.service")),
+            (7, 3, "app", None)
+        ]
+    );
+    assert_eq!(held.files[0].path, files.path().join("data.log"));
+    assert!(held.files[0].bytes >= 64 * 1024);
+    assert_eq!(held.bytes, held.files[0].bytes + held.files[2].bytes + held.files[3].bytes);
+    assert_eq!(held.files[1].fd_path(proc_dir.path()), proc_dir.path().join("43/fd/5"));
+
+    // The file open in two nginx processes is counted once for the service.
+    let holders: Vec<(&str, u64, u64)> =
+        held.holders.iter().map(|holder| (holder.holder.as_str(), holder.files, holder.bytes)).collect();
+    assert_eq!(
+        holders,
+        vec![("nginx.service", 2, held.files[0].bytes + held.files[2].bytes), ("app (7)", 1, held.files[3].bytes)]
+    );
+    let json = serde_json::to_value(&held)?;
+    assert_eq!(json["holders"][1]["holder"], "app (7)");
+    assert_eq!(json["holders"][0]["files"], 2);
+
+    // A memfd object links to `/memfd:<name> (deleted)` but is shared memory, which must not be truncated.
+    // SAFETY: the name is NUL-terminated.
+    let fd = unsafe { libc::memfd_create(c"held-space-test".as_ptr(), 0) };
+    assert!(fd >= 0, "memfd_create failed: {}", io::Error::last_os_error());
+    // SAFETY: the descriptor was just created and is owned by nothing else.
+    let mut memfd = unsafe { File::from_raw_fd(fd) };
+    memfd.write_all(&vec![b'x'; 64 * 1024])?;
+    let held = held::scan(Path::new(procfs::PROC_DIR));
+    let pid = std::process::id();
+    assert!(!held.files.iter().any(|file| file.pid == pid && file.fd == memfd.as_raw_fd() as u32));
+
+    assert_eq!(held::unit_of("0::/system.slice/sshd.service\n"), Some("sshd.service".to_string()));
+    assert_eq!(held::unit_of("1:name=systemd:/system.slice/cron.service/control\n"), Some("cron.service".to_string()));
+    assert_eq!(held::unit_of("0::/user.slice/user-1000.slice/session-2.scope\n"), None);
+    Ok(())
+}
+
+#[test]
+fn test_dev_caches() -> Result<()> {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use devcache::{Cache, CARGO, CARGO_TARGETS, GO, NODE_MODULES};
+
+    let home = tempfile::tempdir()?;
+    let env = |name: &str| match name {
+        "HOME" => Some(home.path().to_path_buf()),
+        "CARGO_HOME" => Some(PathBuf::from("/opt/cargo")),
+        "GOMODCACHE" => Some(PathBuf::from("relative/mod")),
+        _ => None,
+    };
+    let caches = devcache::known_caches(env);
+    assert!(caches.contains(&Cache { tool: CARGO, path: PathBuf::from("/opt/cargo/registry"), read_only: false }));
+    assert!(caches.contains(&Cache { tool: GO, path: home.path().join("go/pkg/mod"), read_only: true }));
+    assert!(caches.iter().any(|cache| cache.path == home.path().join(".cache/pip")));
+    assert_eq!(devcache::known_caches(|_| None), Vec::new());
+
+    // Only directories next to a project manifest count, and hidden directories are skipped.
+    let day = Duration::from_secs(24 * 60 * 60);
+    let now = SystemTime::now();
+    for dir in
+        ["rust/target/debug", "web/node_modules/left-pad", "fresh/node_modules", "other/target", ".hidden/target"]
+    {
+        std::fs::create_dir_all(home.path().join(dir))?;
+    }
+    for manifest in ["rust/Cargo.toml", "web/package.json", "fresh/package.json", ".hidden/Cargo.toml"] {
+        std::fs::write(home.path().join(manifest), "")?;
+    }
+    std::fs::write(home.path().join("rust/target/debug/app"), vec![0; 8192])?;
+    std::fs::write(home.path().join("rust/target/CACHEDIR.TAG"), "Signature: 8a477f597d28d172789f06886806bc55\n")?;
+    File::open(home.path().join("fresh/node_modules"))?.set_modified(now + day * 10)?;
+
+    let found = devcache::find_project_caches(&[home.path().to_path_buf()], day * 5, now + day * 7);
+    let found: Vec<(&str, PathBuf)> = found.iter().map(|cache| (cache.tool.name, cache.path.clone())).collect();
+    assert_eq!(
+        found,
+        vec![
+            (CARGO_TARGETS.name, home.path().join("rust/target")),
+            (NODE_MODULES.name, home.path().join("web/node_modules"))
+        ]
+    );
+
+    let measured = devcache::measure(&[
+        Cache { tool: CARGO_TARGETS, path: home.path().join("rust/target"), read_only: false },
+        Cache { tool: GO, path: home.path().join("missing"), read_only: true },
+    ]);
+    assert_eq!(measured.len(), 1);
+    assert_eq!(measured[0].tool, "cargo-targets");
+    assert!(measured[0].bytes >= 8192);
+
+    // The filesystems of the project roots are measured too, e.g. when /home is a separate one.
+    let command = cli::Commands::CleanDevCaches {
+        tools: Vec::new(),
+        all: true,
+        roots: vec![home.path().to_path_buf()],
+        stale_days: devcache::DEFAULT_STALE_DAYS,
+    };
+    assert!(command.locations().contains(&home.path().to_path_buf()));
+    assert!(!space::Snapshot::take(&command.locations()).filesystems.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_cargo_targets() -> Result<()> {
+    use std::time::{Duration, SystemTime};
+
+    let root = tempfile::tempdir()?;
+    let day = Duration::from_secs(24 * 60 * 60);
+    let now = SystemTime::now();
+    let tag = "Signature: 8a477f597d28d172789f06886806bc55\n# This file is a cache directory tag.\n";
+    for (project, profiles, age) in [
+        ("old", &["debug"][..], 60),
+        ("recent", &["debug", "release", "aarch64-unknown-linux-gnu/debug"][..], 1),
+        ("untagged", &["debug"][..], 60),
+    ] {
+        let project = root.path().join(project);
+        std::fs::create_dir_all(project.join("target"))?;
+        std::fs::write(project.join("Cargo.toml"), "[package]\n")?;
+        if !project.ends_with("untagged") {
+            std::fs::write(project.join("target/CACHEDIR.TAG"), tag)?;
+        }
+        for profile in profiles {
+            let profile = project.join("target").join(profile);
+            std::fs::create_dir_all(profile.join(".fingerprint/app-1234"))?;
+            std::fs::create_dir_all(profile.join("incremental"))?;
+            std::fs::write(profile.join("incremental/query-cache.bin"), vec![0; 4096])?;
+            // The release profile of the recent project was last built long ago.
+            let age = if profile.ends_with("release") { 90 } else { age };
+            for dir in [profile.join(".fingerprint"), profile.join(".fingerprint/app-1234")] {
+                File::open(dir)?.set_modified(now - day * age)?;
+            }
+        }
+    }
+
+    let found = targets::find(&[root.path().to_path_buf()]);
+    let projects: Vec<&std::path::Path> = found.iter().map(|target| target.project.as_path()).collect();
+    assert_eq!(projects, vec![root.path().join("recent"), root.path().join("old")]);
+    let profiles: Vec<&str> = found[0].profiles.iter().map(|profile| profile.name.as_str()).collect();
+    assert_eq!(profiles, vec!["aarch64-unknown-linux-gnu/debug", "debug", "release"]);
+    assert!(found[0].bytes >= 3 * 4096);
+    assert!(found[0].profiles[1].incremental_bytes >= 4096);
+
+    // Whole target directories are removed once stale.
+    let cleanups = targets::select(&found, day * 30, false, now);
+    assert_eq!(cleanups.len(), 1);
+    assert_eq!(cleanups[0].paths, vec![root.path().join("old/target")]);
+    assert_eq!(cleanups[0].bytes, found[1].bytes);
+
+    // Pruning removes stale profiles and the incremental caches of the others.
+    let cleanups = targets::select(&found, day * 30, true, now);
+    let recent = root.path().join("recent/target");
+    assert_eq!(
+        cleanups[0].paths,
+        vec![
+            recent.join("aarch64-unknown-linux-gnu/debug/incremental"),
+            recent.join("debug/incremental"),
+            recent.join("release")
+        ]
+    );
+    assert_eq!(cleanups[1].paths, vec![root.path().join("old/target/debug")]);
+
+    let command = cli::Commands::CargoTargets { roots: vec![root.path().to_path_buf()], older_than: 30, prune: false };
+    assert_eq!(command.locations(), vec![root.path().to_path_buf()]);
+    Ok(())
+}
+
+#[test]
+fn test_duplicates() -> Result<()> {
+    use std::path::PathBuf;
+
+    let dir = tempfile::tempdir()?;
+    let path = |name: &str| dir.path().join(name);
+    std::fs::create_dir(path("copies"))?;
+    let mut contents = vec![7; 100 * 1024];
+    for name in ["a.bin", "copies/a.bin", "copies/b.bin"] {
+        std::fs::write(path(name), &contents)?;
+    }
+    // Equal size and equal first bytes, but a different ending only the full hash tells apart.
+    *contents.last_mut().unwrap() = 8;
+    std::fs::write(path("c.bin"), &contents)?;
+    std::fs::hard_link(path("c.bin"), path("copies/c.bin"))?;
+    for name in ["small.txt", "copies/small.txt"] {
+        std::fs::write(path(name), "hello")?;
+    }
+    std::fs::write(path("empty"), "")?;
+    std::fs::write(path("copies/empty"), "")?;
+
+    let found = duplicates::find(&[dir.path().to_path_buf()], 1);
+    assert_eq!(found.files, 6);
+    let sets: Vec<(u64, Vec<PathBuf>)> = found.sets.iter().map(|set| (set.wasted(), set.paths.clone())).collect();
+    assert_eq!(
+        sets,
+        vec![
+            (200 * 1024, vec![path("a.bin"), path("copies/a.bin"), path("copies/b.bin")]),
+            (5, vec![path("copies/small.txt"), path("small.txt")]),
+        ]
+    );
+    assert_eq!(found.wasted, 200 * 1024 + 5);
+    assert_eq!(duplicates::find(&[dir.path().to_path_buf()], 1024).sets.len(), 1);
+
+    // With an action, the first copy of every set is kept without asking.
+    let runner = FakeRunner::new().expect(
+        &["rm", "-rf", "--", &path("copies/a.bin").to_string_lossy(), &path("copies/b.bin").to_string_lossy()],
+        CmdOutput::ok(""),
+    );
+    let session =
+        session::Session { assume_yes: true, ..session::Session::new(Box::new(runner), backend::Backend::Dnf) };
+    let command = cli::Commands::Duplicates {
+        paths: vec![dir.path().to_path_buf()],
+        action: Some(cli::DuplicateAction::Delete),
+        min_size: Some(String::from("1K")),
+    };
+    command.execute(&session)?;
+
+    let command = cli::Commands::Duplicates {
+        paths: vec![dir.path().to_path_buf()],
+        action: Some(cli::DuplicateAction::Hardlink),
+        min_size: None,
+    };
+    let plan = command.plan(&session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf))?;
+    let steps: Vec<String> = plan.steps.iter().map(ToString::to_string).collect();
+    assert_eq!(steps.len(), 3);
+    assert_str_eq!(
+        steps[2],
+        format!("ln -f -- {} {}", path("copies/small.txt").display(), path("small.txt").display())
+    );
+    assert_eq!(command.locations(), vec![dir.path().to_path_buf()]);
+    Ok(())
+}
+
+#[test]
+fn test_quarantine() -> Result<()> {
+    use std::{
+        os::unix::fs::PermissionsExt,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    let dir = tempfile::tempdir()?;
+    let user_dir = dir.path().join("data/quarantine");
+    let log = dir.path().join("app/old.log");
+    std::fs::create_dir(dir.path().join("app"))?;
+    std::fs::write(&log, "line\n")?;
+    std::fs::set_permissions(&log, std::fs::Permissions::from_mode(0o640))?;
+    let day = Duration::from_secs(24 * 60 * 60);
+    let modified = SystemTime::now() - day * 100;
+    File::options().write(true).open(&log)?.set_modified(modified)?;
+
+    // Items on the filesystem of the user's data directory are quarantined there.
+    assert_eq!(quarantine::dir_for(&log, Some(&user_dir))?, user_dir);
+    let now = SystemTime::now();
+    let entry = quarantine::quarantine(&log, &user_dir, now - day * 10)?;
+    assert!(!log.exists());
+    assert_eq!(entry.manifest.original_path, log);
+    assert_eq!(entry.manifest.mode & 0o777, 0o640);
+    assert_eq!(entry.manifest.modified, modified);
+
+    let entries = quarantine::list(std::slice::from_ref(&user_dir));
+    assert_eq!(entries, vec![entry.clone()]);
+    assert_eq!(quarantine::expired(&entries, day * 30, now), Vec::<&quarantine::Entry>::new());
+    assert_eq!(quarantine::expired(&entries, day * 7, now).len(), 1);
+
+    // Restoring puts the file back with its mode and timestamps, and fails if the path is taken.
+    std::fs::write(&log, "new\n")?;
+    assert!(quarantine::restore(&entry).is_err());
+    std::fs::remove_file(&log)?;
+    quarantine::restore(&entry)?;
+    assert_eq!(std::fs::read_to_string(&log)?, "line\n");
+    let metadata = std::fs::metadata(&log)?;
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+    assert_eq!(metadata.modified()?, modified);
+    assert!(quarantine::list(std::slice::from_ref(&user_dir)).is_empty());
+
+    // Unreadable files and symbolic links get their metadata back too.
+    let secret = dir.path().join("app/secret");
+    std::fs::write(&secret, "key\n")?;
+    std::fs::set_permissions(&secret, std::fs::Permissions::from_mode(0o200))?;
+    File::options().write(true).open(&secret)?.set_modified(modified)?;
+    let link = dir.path().join("app/current.log");
+    std::os::unix::fs::symlink(&log, &link)?;
+    for path in [&secret, &link] {
+        let entry = quarantine::quarantine(path, &user_dir, now)?;
+        quarantine::restore(&entry)?;
+        assert!(!entry.dir.exists());
+        assert_eq!(std::fs::symlink_metadata(path)?.modified()?, entry.manifest.modified);
+    }
+    assert_eq!(std::fs::symlink_metadata(&secret)?.permissions().mode() & 0o777, 0o200);
+    assert_eq!(std::fs::symlink_metadata(&secret)?.modified()?, modified);
+    assert_eq!(std::fs::read_link(&link)?, log);
+
+    let entry = quarantine::quarantine(&log, &user_dir, now)?;
+    quarantine::purge(&entry)?;
+    assert!(!entry.dir.exists() && !log.exists());
+
+    // In quarantine mode, file-based cleanups run the `quarantine` command instead of `rm`.
+    let session = session::Session {
+        quarantine: true,
+        ..session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Dnf)
+    };
+    let step = session.remove_step(&[PathBuf::from("/tmp/a b")]);
+    assert_str_eq!(step.program, quarantine::program());
+    assert_eq!(step.args, vec!["quarantine", "--", "/tmp/a b"]);
+    Ok(())
+}
+
+#[test]
+fn test_history() -> Result<()> {
+    let history_list = "ID | Command line | Date and time    | Action(s) | Altered
+-------------------------------------------------------------------------
+42 | remove foo   | 2023-05-01 10:00 | Removed   |        2
+41 | install foo  | 2023-04-30 09:00 | Install   |        2
+";
+    assert_eq!(backend::parse_dnf_history(history_list), Some(String::from("42")));
+    assert_eq!(backend::parse_dnf_history("ID Command line\n"), None);
+    assert_str_eq!(history::format_time(std::time::SystemTime::UNIX_EPOCH), "1970-01-01 00:00");
+
+    // Removals are recorded with the packages that disappeared and the dnf transaction.
+    let state_dir = tempfile::tempdir()?;
+    let runner = FakeRunner::new()
+        .expect(&["uname", "-r"], CmdOutput::ok("6.4.3\n"))
+        .expect(
+            &["rpm", "-qa", "--queryformat", backend::RPM_QUERY_FORMAT],
+            CmdOutput::ok(
+                "foo\tx86_64\t1.0-1\t1024\t0\nlibfoo\tx86_64\t1.0-1\t1024\t0\nlibfoo\ti686\t1.0-1\t1024\t0\n",
+            ),
+        )
+        .expect(&["dnf", "list", "--installed"], CmdOutput::ok(""))
+        .expect(&["sudo", "dnf", "remove", "--assumeyes", "foo"], CmdOutput::ok(""))
+        .expect(
+            &["rpm", "-qa", "--queryformat", backend::RPM_QUERY_FORMAT],
+            CmdOutput::ok("bar\tx86_64\t2.0-1\t1024\t0\n"),
+        )
+        .expect(&["dnf", "list", "--installed"], CmdOutput::ok(""))
+        .expect(&["dnf", "history", "list"], CmdOutput::ok(history_list));
+    let session = session::Session {
+        assume_yes: true,
+        state_dir: Some(state_dir.path().to_path_buf()),
+        ..session::Session::new(Box::new(runner), backend::Backend::Dnf)
+    };
+    cli::Commands::RemovePackage { packages: vec![String::from("foo")] }.execute(&session)?;
+    let removals = history::removals(state_dir.path())?;
+    assert_eq!(removals.len(), 1);
+    assert_eq!(removals[0].run_id, session.run_id);
+    assert_eq!(removals[0].packages, vec!["foo", "libfoo"]);
+    assert_eq!(removals[0].transaction.as_deref(), Some("42"));
+
+    // Undoing reverts the dnf transaction, or reinstalls the packages on other backends.
+    let undo = cli::Commands::Undo { run_id: session.run_id.clone() };
+    assert_str_eq!(undo.plan(&session)?.steps[0].to_string(), "sudo dnf history undo --assumeyes 42");
+    assert!(cli::Commands::Undo { run_id: String::from("0-0") }.plan(&session).is_err());
+
+    let removal = history::Removal { backend: String::from("apt"), transaction: None, ..removals[0].clone() };
+    history::record(state_dir.path(), &removal)?;
+    let session = session::Session {
+        state_dir: Some(state_dir.path().to_path_buf()),
+        ..session::Session::new(Box::new(FakeRunner::new()), backend::Backend::Apt)
+    };
+    // The run mixes backends now, which cannot be undone with apt alone.
+    assert!(undo.plan(&session).is_err());
+    let step = backend::Backend::Apt.package_manager().undo(None, &["foo", "libfoo"]);
+    assert_str_eq!(step.to_string(), "sudo apt-get install --assume-yes foo libfoo");
+    Ok(())
+}
+
+#[test]
+fn test_run_history() -> Result<()> {
+    use std::time::{Duration, SystemTime};
+
+    let day = Duration::from_secs(24 * 60 * 60);
+    // 2023-05-01 was a Monday.
+    let monday = SystemTime::UNIX_EPOCH + day * 19_478;
+    let task = |name: &str, bytes: i64, errors: &[&str]| report::TaskReport {
+        task: name.to_string(),
+        commands: vec![runner::CommandRecord {
+            argv: vec![String::from("sudo"), String::from("dnf"), String::from("clean"), String::from("all")],
+            exit_code: Some(i32::from(!errors.is_empty())),
+            stdout: String::from("42 files removed"),
+            stderr: String::new(),
+        }],
+        bytes_reclaimed: Some(bytes),
+        details: None,
+        errors: errors.iter().map(|error| error.to_string()).collect(),
+    };
+    let report = |tasks| report::Report { dry_run: false, tasks };
+
+    let state_dir = tempfile::tempdir()?;
+    let runs = [
+        history::Run::new("1", monday, true, &report(vec![task("Clean package cache", 100, &[])])),
+        history::Run::new("2", monday + day * 2, false, &report(vec![task("Clean package cache", 50, &["locked"])])),
+        history::Run::new("3", monday + day * 31, false, &report(vec![task("Clean up log files", 10, &[])])),
+    ];
+    for run in &runs {
+        history::record_run(state_dir.path(), run)?;
+    }
+    let recorded = history::runs(state_dir.path())?;
+    assert_eq!(recorded, runs);
+    // Command output is not kept in the history.
+    assert_eq!(serde_json::to_value(&recorded[0])?["tasks"][0]["commands"][0].get("stdout"), None);
+
+    let filter = |since, task: Option<&str>, failed| {
+        let filter = history::Filter { since, task: task.map(String::from), failed };
+        filter.apply(recorded.clone()).into_iter().map(|run| run.run_id).collect::<Vec<_>>()
+    };
+    assert_eq!(filter(None, None, false), vec!["1", "2", "3"]);
+    assert_eq!(filter(Some(monday + day), None, false), vec!["2", "3"]);
+    assert_eq!(filter(None, Some("LOG"), false), vec!["3"]);
+    assert_eq!(filter(None, None, true), vec!["2"]);
+
+    let summary = history::summarize(&recorded, history::Period::Month);
+    let rows: Vec<_> =
+        summary.iter().map(|row| (row.period.as_str(), row.task.as_str(), row.runs, row.bytes_reclaimed)).collect();
+    assert_eq!(rows, vec![("2023-05-01", "Clean package cache", 2, 150), ("2023-06-01", "Clean up log files", 1, 10)]);
+    let summary = history::summarize(&recorded[1..], history::Period::Week);
+    assert_eq!(summary[0].period, "2023-05-01");
+    Ok(())
+}
+
+#[test]
+fn test_journal_usage() {
+    let output = r#"{"_BOOT_ID":"b1","_SYSTEMD_UNIT":"NetworkManager.service","MESSAGE":"carrier lost"}
+{"_BOOT_ID":"b1","_SYSTEMD_UNIT":"NetworkManager.service","MESSAGE":"carrier restored"}
+{"_BOOT_ID":"b2","SYSLOG_IDENTIFIER":"sudo","MESSAGE":"session opened"}
+{"_BOOT_ID":"b2","_TRANSPORT":"kernel","MESSAGE":[104,105]}
+not json
+"#;
+    let usage = journal::parse_usage(output, 2);
+    assert_eq!(usage.entries, 4);
+    assert_eq!(usage.bytes, 12 + 16 + 14 + 2);
+
+    let units: Vec<(&str, u64, u64)> =
+        usage.units.iter().map(|unit| (unit.name.as_str(), unit.entries, unit.bytes)).collect();
+    assert_eq!(units, vec![("NetworkManager.service", 2, 28), ("sudo", 1, 14)]);
+    let boots: Vec<(&str, u64)> = usage.boots.iter().map(|boot| (boot.name.as_str(), boot.bytes)).collect();
+    assert_eq!(boots, vec![("b1", 28), ("b2", 16)]);
+
+    assert_eq!(journal::parse_disk_usage("No journal files were found."), None);
+    assert_eq!(journal::parse_bytes("3.5G"), Some(3584 * 1024 * 1024));
+    assert_eq!(journal::parse_bytes("8.0M"), Some(8 * 1024 * 1024));
+    assert_eq!(journal::parse_bytes("0B"), Some(0));
+    assert_eq!(journal::parse_bytes("lots"), None);
+}
+
+#[test]
+fn test_space_accounting() {
+    use space::{Filesystem, Snapshot, Usage};
+
+    let snapshot = |root_free: u64, boot_free: u64| Snapshot {
+        filesystems: vec![
+            Filesystem {
+                device: 1,
+                path: String::from("/"),
+                usage: Usage { total: 100, free: root_free, available: 0 },
+            },
+            Filesystem {
+                device: 2,
+                path: String::from("/boot"),
+                usage: Usage { total: 10, free: boot_free, available: 0 },
+            },
+        ],
+    };
+    let before = snapshot(40, 5);
+    let after = snapshot(55, 3);
+    assert_eq!(after.reclaimed_since(&before), 13);
+    assert_eq!(before.reclaimed_since(&after), -13);
+
+    let task = |bytes| report::TaskReport { bytes_reclaimed: bytes, ..Default::default() };
+    let report = report::Report { dry_run: false, tasks: vec![task(Some(2048)), task(None), task(Some(-24))] };
+    assert_eq!(report.total(), 2024);
+
+    assert!(!Snapshot::take(&["/", "/nonexistent-path"]).filesystems.is_empty());
+}
+
+#[test]
+fn test_format_bytes() {
+    assert_str_eq!(space::format_bytes(0), "0 B");
+    assert_str_eq!(space::format_bytes(1023), "1023 B");
+    assert_str_eq!(space::format_bytes(1536), "1.5 KiB");
+    assert_str_eq!(space::format_bytes(10 * 1024 * 1024 * 1024), "10.0 GiB");
+    assert_str_eq!(space::format_signed_bytes(-2048), "-2.0 KiB");
+}
+
+#[test]
+fn test_analyze_scan() -> Result<()> {
+    use analyze::ScanOptions;
+
+    let root = tempfile::tempdir()?;
+    std::fs::create_dir_all(root.path().join("a/b"))?;
+    std::fs::create_dir(root.path().join("c"))?;
+    std::fs::write(root.path().join("a/b/big"), vec![1u8; 64 * 1024])?;
+    std::fs::write(root.path().join("a/small"), vec![1u8; 100])?;
+    std::fs::write(root.path().join("c/medium"), vec![1u8; 8 * 1024])?;
+    std::fs::hard_link(root.path().join("c/medium"), root.path().join("c/medium-link"))?;
+
+    let analysis = analyze::scan(root.path(), ScanOptions { one_file_system: true, top: 2 })?;
+    assert_eq!(analysis.directory_count, 4);
+    assert_eq!(analysis.file_count, 4);
+    assert_eq!(analysis.errors, 0);
+
+    // Hard links are only counted once. Directory entries themselves add to the apparent size.
+    let dir_sizes: u64 =
+        ["", "a", "a/b", "c"].iter().map(|dir| std::fs::metadata(root.path().join(dir)).unwrap().len()).sum();
+    assert_eq!(analysis.root.size.apparent, 64 * 1024 + 100 + 8 * 1024 + dir_sizes);
 
-    let output = execute_cmd("git", &["status"])?;
-    assert!(!output.is_empty());
+    let dirs: Vec<_> = analysis.directories.iter().map(|entry| entry.path.clone()).collect();
+    assert_eq!(dirs, vec![root.path().to_path_buf(), root.path().join("a")]);
+    assert_eq!(analysis.files.len(), 2);
+    assert_eq!(analysis.files[0].path, root.path().join("a/b/big"));
     Ok(())
 }
 
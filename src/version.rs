@@ -0,0 +1,88 @@
+//! Package version comparison.
+//!
+//! Package managers list installed kernels in whatever order their database returns them, which
+//! is not necessarily oldest first. Versions are compared with the algorithm of `rpmvercmp(3)`, so
+//! that `6.10.1` sorts after `6.9.12` and pre-releases such as `6.5.0~rc1` sort before `6.5.0`.
+
+use std::cmp::Ordering;
+
+/// Compares two version strings the way `rpmvercmp(3)` does.
+///
+/// Both strings are split into runs of digits and runs of letters, skipping every other character.
+/// Numeric runs compare as numbers and are newer than alphabetic runs. A `~` sorts before anything,
+/// even the end of the string, while a `^` sorts after the end of the string but before anything
+/// else.
+pub fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let is_separator = |c: &u8| !c.is_ascii_alphanumeric() && *c != b'~' && *c != b'^';
+    let (mut one, mut two) = (a.as_bytes(), b.as_bytes());
+
+    loop {
+        while one.first().is_some_and(is_separator) {
+            one = &one[1..];
+        }
+        while two.first().is_some_and(is_separator) {
+            two = &two[1..];
+        }
+
+        // Tilde sorts before everything else.
+        match (one.first(), two.first()) {
+            (Some(b'~'), Some(b'~')) => {
+                (one, two) = (&one[1..], &two[1..]);
+                continue;
+            }
+            (Some(b'~'), _) => return Ordering::Less,
+            (_, Some(b'~')) => return Ordering::Greater,
+            _ => {}
+        }
+
+        // Caret sorts after the end of the string, but before anything else.
+        match (one.first(), two.first()) {
+            (Some(b'^'), Some(b'^')) => {
+                (one, two) = (&one[1..], &two[1..]);
+                continue;
+            }
+            (Some(b'^'), None) => return Ordering::Greater,
+            (None, Some(b'^')) => return Ordering::Less,
+            (Some(b'^'), _) => return Ordering::Less,
+            (_, Some(b'^')) => return Ordering::Greater,
+            _ => {}
+        }
+
+        if one.is_empty() || two.is_empty() {
+            break;
+        }
+
+        let numeric = one[0].is_ascii_digit();
+        let in_segment = |c: &u8| if numeric { c.is_ascii_digit() } else { c.is_ascii_alphabetic() };
+        let (segment_one, rest_one) = one.split_at(one.iter().position(|c| !in_segment(c)).unwrap_or(one.len()));
+        let (segment_two, rest_two) = two.split_at(two.iter().position(|c| !in_segment(c)).unwrap_or(two.len()));
+        (one, two) = (rest_one, rest_two);
+
+        // The segments are of different types. Numeric segments are newer than alphabetic ones.
+        if segment_two.is_empty() {
+            return if numeric { Ordering::Greater } else { Ordering::Less };
+        }
+
+        let ordering = if numeric {
+            let trim = |segment: &[u8]| -> usize { segment.iter().position(|&c| c != b'0').unwrap_or(segment.len()) };
+            let (segment_one, segment_two) = (&segment_one[trim(segment_one)..], &segment_two[trim(segment_two)..]);
+            segment_one.len().cmp(&segment_two.len()).then_with(|| segment_one.cmp(segment_two))
+        } else {
+            segment_one.cmp(segment_two)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    // Whichever string has segments left over is newer.
+    match (one.is_empty(), two.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, _) => Ordering::Greater,
+    }
+}